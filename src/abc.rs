@@ -0,0 +1,1217 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::lexer::Span;
+use crate::note::{Note, Pitch, DEFAULT_AMPLITUDE};
+use crate::parser::{ArgumentParseError, ParseErrors};
+use crate::tuning::Tuning;
+use crate::value::NoteValue;
+use crate::REFERENCE_OCTAVE;
+
+/// Note letters in the order of their index in a key signature.
+const LETTERS: [char; 7] = ['C', 'D', 'E', 'F', 'G', 'A', 'B'];
+
+/// Note letters in the order that a key signature adds sharps to them. Flats are added in the
+/// reverse order.
+const ORDER_OF_SHARPS: [char; 7] = ['F', 'C', 'G', 'D', 'A', 'E', 'B'];
+
+fn letter_index(letter: char) -> usize {
+    LETTERS
+        .iter()
+        .position(|&l| l == letter.to_ascii_uppercase())
+        .expect("Note letter must be from A-G")
+}
+
+/// Whether `source` is an ABC tune, i.e. its first line that is neither blank nor a `%`
+/// comment is an `X:` field.
+pub fn is_abc(source: &str) -> bool {
+    source
+        .lines()
+        .map(str::trim_start)
+        .find(|line| !line.is_empty() && !line.starts_with('%'))
+        .is_some_and(|line| line.starts_with("X:"))
+}
+
+/// Parse a fraction such as `1/8`, or a whole number such as `1`.
+fn parse_fraction(s: &str) -> Option<NoteValue> {
+    let (numerator, denominator) = s.split_once('/').unwrap_or((s, "1"));
+    let numerator = numerator.trim().parse::<u64>().ok()?;
+    let denominator = denominator.trim().parse::<u64>().ok()?;
+    (numerator != 0 && denominator != 0).then(|| NoteValue::new(numerator, denominator))
+}
+
+/// Parse the key signature of a `K:` field, e.g. `G`, `Dm`, `Ador` or `Bb mix ^c`, into the
+/// accidental of every note letter.
+fn parse_key(s: &str) -> Result<[i32; 7], String> {
+    let invalid = || {
+        format!(
+            "Invalid key 'K:{s}'. Must be a tonic from A-G, optionally followed by # or b and a \
+            mode, e.g. K:G, K:Bbm or K:Ddor"
+        )
+    };
+    let mut key = [0; 7];
+    let mut words = s.split_whitespace().peekable();
+    let tonic = match words.peek() {
+        Some(word) if word.starts_with(|c: char| ('A'..='G').contains(&c)) => words.next(),
+        Some(word) if word.eq_ignore_ascii_case("none") || *word == "HP" => {
+            words.next();
+            None
+        }
+        _ => None,
+    };
+
+    if let Some(tonic) = tonic {
+        let mut chars = tonic.chars();
+        let letter = chars.next().unwrap();
+        let rest = chars.as_str();
+        let (accidental, mode) = match rest.chars().next() {
+            Some('#') => (1, &rest[1..]),
+            Some('b') => (-1, &rest[1..]),
+            _ => (0, rest),
+        };
+        let mode = match mode.is_empty() {
+            true => match words.peek() {
+                Some(word) if !word.contains(['=', '^', '_']) => words.next().unwrap(),
+                _ => "",
+            },
+            false => mode,
+        };
+        let mode = mode.to_ascii_lowercase();
+        // Number of fifths the mode is away from the major mode of the same tonic
+        let mode_fifths = match mode.get(..3).unwrap_or(&mode) {
+            "" | "maj" | "ion" => 0,
+            "m" | "min" | "aeo" => -3,
+            "mix" => -1,
+            "dor" => -2,
+            "phr" => -4,
+            "lyd" => 1,
+            "loc" => -5,
+            _ => return Err(invalid()),
+        };
+        let letter_fifths = ORDER_OF_SHARPS.iter().position(|&l| l == letter).unwrap() as i32 - 1;
+        let fifths = letter_fifths + 7 * accidental + mode_fifths;
+
+        for i in 0..fifths.unsigned_abs() as usize {
+            match fifths > 0 {
+                true => key[letter_index(ORDER_OF_SHARPS[i % 7])] += 1,
+                false => key[letter_index(ORDER_OF_SHARPS[6 - i % 7])] -= 1,
+            }
+        }
+    }
+
+    // Explicit accidentals after the key, e.g. `^f _b`, and clef options such as `clef=bass`
+    for word in words {
+        let accidental = match word.chars().next() {
+            Some('^') => 1,
+            Some('_') => -1,
+            Some('=') => 0,
+            _ if word.contains('=') => continue,
+            _ => return Err(invalid()),
+        };
+        match word[1..].chars().next() {
+            Some(letter @ ('A'..='G' | 'a'..='g')) if word.len() == 2 => {
+                key[letter_index(letter)] = accidental
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(key)
+}
+
+/// Element of a tune, in the order it is written.
+enum Item {
+    Note {
+        /// Pitches sounding together, none for a rest.
+        pitches: Vec<Pitch>,
+        value: NoteValue,
+        /// Whether the note is tied to the next one.
+        tie: bool,
+        span: Span,
+    },
+    /// Tempo of `bpm` beats per minute, where `scale` converts note values into quarter beats.
+    Tempo {
+        bpm: u32,
+        scale: NoteValue,
+    },
+    RepeatStart,
+    RepeatEnd,
+    /// End of a section, e.g. `||` or `|]`.
+    SectionEnd,
+    /// Start of an ending played on the listed passes, e.g. `[1` or `:|2`.
+    Ending(Vec<u32>),
+}
+
+/// Parser of a single ABC tune. Like the sequence parser, errors are collected rather than
+/// returned, so that every error of the tune can be reported at once.
+struct AbcParser<'a> {
+    errors: Vec<ArgumentParseError>,
+    items: Vec<Item>,
+    /// Line being parsed, its offset in the source and the position within it.
+    line: &'a str,
+    base: usize,
+    pos: usize,
+    /// Length of a note without a length, set by the `L:` field.
+    unit: Option<NoteValue>,
+    /// Length of a bar, or `None` for free meter.
+    meter: Option<NoteValue>,
+    /// Whether the meter is compound, e.g. 6/8, which is lost when its length is reduced.
+    compound: bool,
+    /// Accidental of every note letter from the key signature, indexed from C.
+    key: [i32; 7],
+    /// Accidentals written earlier in the current bar, by note letter and octave.
+    bar_accidentals: HashMap<(char, i32), i32>,
+    /// Scale and number of remaining notes of the current tuplet.
+    tuplet: Option<(NoteValue, u64)>,
+    /// Scale of the next note, set by a broken rhythm such as `A>B`.
+    broken: Option<NoteValue>,
+    /// Index of the item of the last note.
+    last_note: Option<usize>,
+    in_body: bool,
+}
+
+impl<'a> AbcParser<'a> {
+    fn new(tempo: u32) -> Self {
+        AbcParser {
+            errors: Vec::new(),
+            items: vec![Item::Tempo {
+                bpm: tempo,
+                scale: NoteValue::whole(),
+            }],
+            line: "",
+            base: 0,
+            pos: 0,
+            unit: None,
+            meter: Some(NoteValue::whole()),
+            compound: false,
+            key: [0; 7],
+            bar_accidentals: HashMap::new(),
+            tuplet: None,
+            broken: None,
+            last_note: None,
+            in_body: false,
+        }
+    }
+
+    fn error(&mut self, msg: &str, start: usize) {
+        let span = Span::new(start, self.here().max(start + 1));
+        self.errors.push(ArgumentParseError::new(msg).at_span(span));
+    }
+
+    /// Note value of `lengths` times the unit note length.
+    fn note_value(
+        &self,
+        lengths: &[NoteValue],
+        start: usize,
+    ) -> Result<NoteValue, ArgumentParseError> {
+        lengths
+            .iter()
+            .try_fold(self.unit(), |value, &length| value.checked_mul(length))
+            .ok_or_else(|| {
+                ArgumentParseError::new("Note is too long or too finely divided")
+                    .at_span(Span::new(start, self.here()))
+            })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.line[self.pos..].chars().next()
+    }
+
+    fn peek_at(&self, n: usize) -> Option<char> {
+        self.line[self.pos..].chars().nth(n)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += c.len_utf8();
+        }
+        found
+    }
+
+    /// Position in the source of the next character.
+    fn here(&self) -> usize {
+        self.base + self.pos
+    }
+
+    fn digits(&mut self) -> Option<u64> {
+        let len = self.line[self.pos..]
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.line.len() - self.pos);
+        let digits = &self.line[self.pos..self.pos + len];
+        self.pos += len;
+        (!digits.is_empty()).then(|| digits.parse().unwrap_or(u64::MAX))
+    }
+
+    /// Note value of a note without a length. Unless set by the `L:` field, it is a
+    /// sixteenth in meters shorter than 3/4 and an eighth otherwise.
+    fn unit(&self) -> NoteValue {
+        self.unit.unwrap_or(match self.meter {
+            Some(meter) if meter.numerator() * 4 < meter.denominator() * 3 => NoteValue::new(1, 16),
+            _ => NoteValue::new(1, 8),
+        })
+    }
+
+    fn parse_tune(&mut self, source: &'a str) {
+        let mut started = false;
+        let mut offset = 0;
+        for line in source.split('\n') {
+            self.base = offset;
+            self.pos = 0;
+            offset += line.len() + 1;
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            self.line = match line.find('%') {
+                Some(comment) => &line[..comment],
+                None => line,
+            };
+
+            if self.line.trim().is_empty() {
+                // A blank line ends the tune
+                if self.in_body {
+                    return;
+                }
+                continue;
+            }
+            let bytes = self.line.as_bytes();
+            if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+                // Another X: field starts the next tune
+                if bytes[0] == b'X' && started {
+                    return;
+                }
+                started = true;
+                let line = self.line;
+                self.parse_field(bytes[0] as char, &line[2..], self.base + 2);
+                continue;
+            }
+            started = true;
+            self.in_body = true;
+            self.parse_music();
+        }
+    }
+
+    /// Parse the value of a header field such as `L:1/8`, either on a line of its own or
+    /// inline in brackets, e.g. `[K:D]`, where the value starts at `start` in the source.
+    fn parse_field(&mut self, field: char, value: &str, start: usize) {
+        let start = start + value.len() - value.trim_start().len();
+        let value = value.trim();
+        let result = match field {
+            'L' => match parse_fraction(value) {
+                Some(unit) => {
+                    self.unit = Some(unit);
+                    Ok(())
+                }
+                None => Err(format!(
+                    "Invalid unit note length 'L:{value}'. Must be a fraction, e.g. L:1/8"
+                )),
+            },
+            'M' => self.parse_meter(value),
+            'Q' => self.parse_tempo(value),
+            'K' => parse_key(value).map(|key| {
+                self.key = key;
+                self.in_body = true;
+            }),
+            'V' => Err(String::from(
+                "ABC tunes with several voices (V:) are not supported",
+            )),
+            // Other fields, e.g. the title T: or composer C:, do not affect playback
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            let span = Span::new(start, start + value.len().max(1));
+            self.errors.push(ArgumentParseError::new(&e).at_span(span));
+        }
+    }
+
+    fn parse_meter(&mut self, s: &str) -> Result<(), String> {
+        (self.meter, self.compound) = match s {
+            "" | "none" => (None, false),
+            "C" => (Some(NoteValue::whole()), false),
+            "C|" => (Some(NoteValue::new(2, 2)), false),
+            _ => {
+                let invalid = || format!("Invalid meter 'M:{s}'. Must be a fraction, e.g. M:6/8");
+                let (beats, beat) = s.split_once('/').ok_or_else(invalid)?;
+                // Complex meters add up their beats, e.g. M:(2+3)/8
+                let beats = beats
+                    .trim_matches(|c| c == '(' || c == ')')
+                    .split('+')
+                    .map(|beats| beats.trim().parse::<u64>().ok())
+                    .sum::<Option<u64>>()
+                    .ok_or_else(invalid)?;
+                let beat = beat.trim().parse::<u64>().map_err(|_| invalid())?;
+                if beats == 0 || beat == 0 {
+                    return Err(invalid());
+                }
+                (
+                    Some(NoteValue::new(beats, beat)),
+                    beats % 3 == 0 && beats > 3,
+                )
+            }
+        };
+        Ok(())
+    }
+
+    /// Parse a `Q:` field, e.g. `Q:1/4=120`, `Q:"Allegro" 3/8=80` or `Q:120`, which counts
+    /// beats of the unit note length.
+    fn parse_tempo(&mut self, s: &str) -> Result<(), String> {
+        let invalid =
+            || format!("Invalid tempo 'Q:{s}'. Must be a beat and a tempo, e.g. Q:1/4=120");
+        // Drop text such as "Allegro"
+        let mut text = String::new();
+        for (i, part) in s.split('"').enumerate() {
+            if i % 2 == 0 {
+                text += part;
+            }
+        }
+        let text = text.trim();
+        if text.is_empty() {
+            return Ok(());
+        }
+
+        let (beat, bpm) = match text.split_once('=') {
+            Some((beats, bpm)) => (
+                beats
+                    .split_whitespace()
+                    .map(parse_fraction)
+                    .try_fold(NoteValue::zero(), |sum, beat| sum.checked_add(beat?))
+                    .ok_or_else(invalid)?,
+                bpm,
+            ),
+            None => (self.unit(), text),
+        };
+        let bpm = match bpm.trim().parse::<u32>() {
+            Ok(bpm) if bpm > 0 && beat != NoteValue::zero() => bpm,
+            _ => return Err(invalid()),
+        };
+        // A note value lasts as long as the same number of quarter notes at the tempo in
+        // quarter notes per minute
+        let scale = NoteValue::new(beat.denominator(), beat.numerator())
+            .checked_mul(NoteValue::new(1, 4))
+            .ok_or_else(invalid)?;
+        self.items.push(Item::Tempo { bpm, scale });
+        Ok(())
+    }
+
+    /// Parse a line of the tune body.
+    fn parse_music(&mut self) {
+        while let Some(c) = self.peek() {
+            let start = self.here();
+            match c {
+                '"' => self.skip_delimited('"', "Annotation is missing its closing '\"'"),
+                '!' => self.skip_delimited('!', "Decoration is missing its closing '!'"),
+                '+' => self.skip_delimited('+', "Decoration is missing its closing '+'"),
+                '{' => self.skip_delimited('}', "Grace notes are missing their closing '}'"),
+                '(' => {
+                    self.bump();
+                    if self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                        self.parse_tuplet(start);
+                    }
+                }
+                '-' => {
+                    self.bump();
+                    self.parse_tie(start);
+                }
+                '>' | '<' => self.parse_broken_rhythm(),
+                '|' | ':' => self.parse_bar(),
+                '[' => match (self.peek_at(1), self.peek_at(2)) {
+                    (Some('|'), _) => self.parse_bar(),
+                    (Some(c), _) if c.is_ascii_digit() => {
+                        self.bump();
+                        self.parse_ending();
+                    }
+                    (Some(c), Some(':')) if c.is_ascii_alphabetic() => self.parse_inline_field(),
+                    _ => self.parse_chord(),
+                },
+                '^' | '_' | '=' | 'A'..='G' | 'a'..='g' | 'z' | 'x' | 'Z' | 'X' => {
+                    self.parse_note()
+                }
+                // Slurs, decorations, spacers and line continuations do not affect playback
+                ' ' | '\t' | '`' | ')' | '.' | '~' | 'H' | 'L' | 'M' | 'O' | 'P' | 'S' | 'T'
+                | 'u' | 'v' | 'y' | '\\' | '$' => {
+                    self.bump();
+                }
+                _ => {
+                    self.bump();
+                    self.error(&format!("Unexpected character '{c}' in ABC tune"), start);
+                }
+            }
+        }
+    }
+
+    fn skip_delimited(&mut self, end: char, msg: &str) {
+        let start = self.here();
+        self.bump();
+        match self.line[self.pos..].find(end) {
+            Some(i) => self.pos += i + end.len_utf8(),
+            None => {
+                self.pos = self.line.len();
+                self.error(msg, start);
+            }
+        }
+    }
+
+    fn parse_inline_field(&mut self) {
+        let start = self.here();
+        self.bump();
+        let field = self.bump().unwrap();
+        self.bump();
+        match self.line[self.pos..].find(']') {
+            Some(len) => {
+                let line = self.line;
+                self.parse_field(field, &line[self.pos..self.pos + len], self.here());
+                self.pos += len + 1;
+            }
+            None => {
+                self.pos = self.line.len();
+                self.error("Inline field is missing its closing ']'", start);
+            }
+        }
+    }
+
+    /// Parse a tuplet `(p:q:r`, which plays the next r notes in the time of q. Both q and r
+    /// are optional, where q depends on p and the meter, and r defaults to p.
+    fn parse_tuplet(&mut self, start: usize) {
+        let p = self.digits().unwrap_or(0);
+        let q = match self.eat(':') {
+            true => self.digits(),
+            false => None,
+        };
+        let r = match self.eat(':') {
+            true => self.digits(),
+            false => None,
+        };
+        if p < 2 || q == Some(0) || r == Some(0) {
+            return self.error(
+                "Invalid tuplet. Must be (p, (p:q or (p:q:r with numbers of at least 1, e.g. (3",
+                start,
+            );
+        }
+        let q = q.unwrap_or(match p {
+            2 | 4 | 8 => 3,
+            3 | 6 => 2,
+            _ if self.compound => 3,
+            _ => 2,
+        });
+        self.tuplet = Some((NoteValue::new(q, p), r.unwrap_or(p)));
+    }
+
+    fn parse_tie(&mut self, start: usize) {
+        match self.last_note.map(|i| &mut self.items[i]) {
+            Some(Item::Note { tie, .. }) => *tie = true,
+            _ => self.error("Tie is missing a note to tie from", start),
+        }
+    }
+
+    /// Parse a broken rhythm such as `A>B`, which lengthens the previous note by a dot and
+    /// shortens the next one by as much, or `A<B` which does the opposite. Each additional
+    /// `>` or `<` adds another dot.
+    fn parse_broken_rhythm(&mut self) {
+        let start = self.here();
+        let symbol = self.bump().unwrap();
+        let mut dots = 1;
+        while self.eat(symbol) {
+            dots += 1;
+        }
+        if dots > 8 {
+            return self.error("Broken rhythm has too many dots", start);
+        }
+        let long = NoteValue::dotted(dots);
+        let short = NoteValue::new(1, 1 << dots);
+        let (previous, next) = match symbol {
+            '>' => (long, short),
+            _ => (short, long),
+        };
+        match self.last_note.map(|i| &mut self.items[i]) {
+            Some(Item::Note { value, .. }) => match value.checked_mul(previous) {
+                Some(lengthened) => {
+                    *value = lengthened;
+                    self.broken = Some(next);
+                }
+                None => self.error("Note is too long or too finely divided", start),
+            },
+            _ => self.error("Broken rhythm is missing a note before it", start),
+        }
+    }
+
+    /// Parse a bar line, which can end a repeat with leading colons, start one with trailing
+    /// colons and start an ending with trailing numbers, e.g. `:|2`.
+    fn parse_bar(&mut self) {
+        let start = self.here();
+        let mut bar = String::new();
+        while let Some(c) = self.peek() {
+            let continues = match c {
+                '|' | ':' => true,
+                ']' => bar.ends_with('|'),
+                '[' => bar.is_empty() && self.peek_at(1) == Some('|'),
+                _ => false,
+            };
+            if !continues {
+                break;
+            }
+            bar.push(c);
+            self.bump();
+        }
+        if bar == ":" {
+            return self.error("Unexpected ':' outside of a bar line", start);
+        }
+
+        self.bar_accidentals.clear();
+        if bar.starts_with(':') {
+            self.items.push(Item::RepeatEnd);
+        }
+        if bar.contains("||") || bar.contains("|]") || bar.contains("[|") {
+            self.items.push(Item::SectionEnd);
+        }
+        if bar.ends_with(':') {
+            self.items.push(Item::RepeatStart);
+        }
+        if bar.ends_with('|') && self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.parse_ending();
+        }
+    }
+
+    /// Parse the passes of an ending, e.g. `1`, `1,3` or `1-3`.
+    fn parse_ending(&mut self) {
+        let start = self.here();
+        let mut passes = Vec::new();
+        loop {
+            let Some(first) = self.digits() else {
+                return self.error("Ending is missing the number of its pass", start);
+            };
+            let last = match self.eat('-') {
+                true => self.digits().unwrap_or(first),
+                false => first,
+            };
+            if last > first + 16 {
+                return self.error("Ending spans too many passes", start);
+            }
+            passes.extend((first..=last).map(|pass| pass as u32));
+            if !self.eat(',') {
+                break;
+            }
+        }
+        self.items.push(Item::Ending(passes));
+    }
+
+    /// Parse the accidental, letter and octave of a note, where an accidental applies to the
+    /// same note for the rest of the bar.
+    fn parse_pitch(&mut self) -> Result<Pitch, ArgumentParseError> {
+        let start = self.here();
+        let accidental = match self.peek() {
+            Some('^') => Some(if self.eat('^') && self.eat('^') { 2 } else { 1 }),
+            Some('_') => Some(if self.eat('_') && self.eat('_') {
+                -2
+            } else {
+                -1
+            }),
+            Some('=') => {
+                self.bump();
+                Some(0)
+            }
+            _ => None,
+        };
+        let letter = match self.bump() {
+            Some(letter @ ('A'..='G' | 'a'..='g')) => letter,
+            _ => {
+                return Err(ArgumentParseError::new("Accidental is missing its note")
+                    .at_span(Span::new(start, self.here())))
+            }
+        };
+
+        let mut octave = match letter.is_ascii_uppercase() {
+            true => REFERENCE_OCTAVE,
+            false => REFERENCE_OCTAVE + 1,
+        };
+        loop {
+            match self.peek() {
+                Some('\'') => octave += 1,
+                Some(',') => octave -= 1,
+                _ => break,
+            }
+            self.bump();
+        }
+
+        let letter = letter.to_ascii_uppercase();
+        let accidental = match accidental {
+            Some(accidental) => {
+                self.bar_accidentals.insert((letter, octave), accidental);
+                accidental
+            }
+            None => match self.bar_accidentals.get(&(letter, octave)) {
+                Some(&accidental) => accidental,
+                None => self.key[letter_index(letter)],
+            },
+        };
+        Pitch::new(letter, accidental, octave)
+            .map_err(|e| ArgumentParseError::new(&e).at_span(Span::new(start, self.here())))
+    }
+
+    /// Parse the length of a note as a multiple of the unit note length, e.g. `3`, `/`, `//`,
+    /// `/4` or `3/2`.
+    fn parse_length(&mut self) -> Result<NoteValue, ArgumentParseError> {
+        let start = self.here();
+        let numerator = self.digits().unwrap_or(1);
+        let mut denominator: u64 = 1;
+        while self.eat('/') {
+            denominator = denominator.saturating_mul(self.digits().unwrap_or(2));
+        }
+        if numerator == 0 || denominator == 0 {
+            return Err(ArgumentParseError::new("Note length must not be zero")
+                .at_span(Span::new(start, self.here())));
+        }
+        Ok(NoteValue::new(numerator, denominator))
+    }
+
+    fn parse_note(&mut self) {
+        let start = self.here();
+        let note = match self.peek() {
+            Some('z' | 'x') => {
+                self.bump();
+                self.parse_length()
+                    .and_then(|length| Ok((Vec::new(), self.note_value(&[length], start)?)))
+            }
+            // Rest of a number of whole bars
+            Some('Z' | 'X') => {
+                self.bump();
+                let bars = self.digits().unwrap_or(1);
+                let span = Span::new(start, self.here());
+                match self
+                    .meter
+                    .map(|meter| meter.checked_mul(NoteValue::new(bars, 1)))
+                {
+                    Some(Some(value)) => Ok((Vec::new(), value)),
+                    Some(None) => Err(ArgumentParseError::new("Rest is too long").at_span(span)),
+                    None => Err(ArgumentParseError::new(
+                        "Rest of whole bars requires a meter, e.g. M:4/4",
+                    )
+                    .at_span(span)),
+                }
+            }
+            _ => self.parse_pitch().and_then(|pitch| {
+                let length = self.parse_length()?;
+                Ok((vec![pitch], self.note_value(&[length], start)?))
+            }),
+        };
+        match note {
+            Ok((pitches, value)) => self.push_note(pitches, value, start),
+            Err(e) => self.errors.push(e),
+        }
+    }
+
+    /// Parse a chord such as `[CEG]2`, whose length is that of its first note times the
+    /// length after the closing bracket.
+    fn parse_chord(&mut self) {
+        let start = self.here();
+        self.bump();
+        let (mut pitches, mut length, mut tie) = (Vec::new(), None, false);
+        loop {
+            let note_start = self.here();
+            match self.peek() {
+                None => return self.error("Chord is missing its closing ']'", start),
+                Some(']') => {
+                    self.bump();
+                    break;
+                }
+                Some('-') => {
+                    self.bump();
+                    tie = true;
+                }
+                Some(' ' | '\t') => {
+                    self.bump();
+                }
+                Some('^' | '_' | '=' | 'A'..='G' | 'a'..='g') => {
+                    let note = self
+                        .parse_pitch()
+                        .and_then(|pitch| Ok((pitch, self.parse_length()?)));
+                    match note {
+                        Ok((pitch, note_length)) => {
+                            pitches.push(pitch);
+                            length.get_or_insert(note_length);
+                        }
+                        Err(e) => self.errors.push(e),
+                    }
+                }
+                Some(c) => {
+                    self.bump();
+                    self.error(&format!("Unexpected character '{c}' in chord"), note_start);
+                }
+            }
+        }
+        let Some(length) = length else {
+            return self.error("Chord must contain at least one note", start);
+        };
+        let value = self
+            .parse_length()
+            .and_then(|chord_length| self.note_value(&[length, chord_length], start));
+        match value {
+            Ok(value) => {
+                self.push_note(pitches, value, start);
+                if tie {
+                    self.parse_tie(start);
+                }
+            }
+            Err(e) => self.errors.push(e),
+        }
+    }
+
+    /// Add a note, scaled by any broken rhythm and tuplet it is part of.
+    fn push_note(&mut self, pitches: Vec<Pitch>, value: NoteValue, start: usize) {
+        let mut value = Some(value);
+        if let Some(scale) = self.broken.take() {
+            value = value.and_then(|value| value.checked_mul(scale));
+        }
+        if let Some((scale, remaining)) = self.tuplet {
+            value = value.and_then(|value| value.checked_mul(scale));
+            self.tuplet = (remaining > 1).then_some((scale, remaining - 1));
+        }
+        let span = Span::new(start, self.here());
+        match value {
+            Some(value) => {
+                self.last_note = Some(self.items.len());
+                self.items.push(Item::Note {
+                    pitches,
+                    value,
+                    tie: false,
+                    span,
+                });
+            }
+            None => self.error("Note is too long or too finely divided", start),
+        }
+    }
+
+    /// The items of the tune in the order they are played, with repeats and endings
+    /// unfolded. A repeat goes back to the last `|:`, the end of the last repeat or the start
+    /// of the tune.
+    fn unfold_repeats(&self) -> Vec<&Item> {
+        let mut played = Vec::new();
+        let (mut i, mut start, mut pass) = (0, 0, 1);
+        let mut repeated = HashSet::new();
+        let (mut in_ending, mut skipping) = (false, false);
+        while i < self.items.len() {
+            match &self.items[i] {
+                Item::RepeatStart => {
+                    (start, pass) = (i + 1, 1);
+                    (in_ending, skipping) = (false, false);
+                }
+                Item::RepeatEnd if repeated.insert(i) => {
+                    (i, pass) = (start, pass + 1);
+                    skipping = false;
+                    continue;
+                }
+                Item::RepeatEnd => {
+                    start = i + 1;
+                    if !in_ending {
+                        pass = 1;
+                    }
+                }
+                Item::SectionEnd => {
+                    if in_ending {
+                        start = i + 1;
+                    }
+                    pass = 1;
+                    (in_ending, skipping) = (false, false);
+                }
+                Item::Ending(passes) => {
+                    in_ending = true;
+                    skipping = !passes.contains(&pass);
+                }
+                item => {
+                    if !skipping {
+                        played.push(item);
+                    }
+                }
+            }
+            i += 1;
+        }
+        played
+    }
+
+    /// Convert the played items into notes, merging tied notes of the same pitches.
+    fn get_notes(&mut self, tuning: &Tuning, sample_rate: u32) -> Vec<Note> {
+        let mut notes: Vec<Note> = Vec::new();
+        let mut errors = Vec::new();
+        let (mut bpm, mut scale) = (1, NoteValue::whole());
+        // Round the absolute positions rather than each note value, so that rounding errors
+        // do not accumulate over the tune. Positions restart from a new tempo.
+        let (mut tempo_start, mut position) = (0, NoteValue::zero());
+        let mut tied = false;
+        for item in self.unfold_repeats() {
+            let (pitches, value, tie, span) = match item {
+                Item::Tempo {
+                    bpm: new_bpm,
+                    scale: new_scale,
+                } => {
+                    tempo_start += position.to_samples(bpm, sample_rate);
+                    (bpm, scale, position) = (*new_bpm, *new_scale, NoteValue::zero());
+                    continue;
+                }
+                Item::Note {
+                    pitches,
+                    value,
+                    tie,
+                    span,
+                } => (pitches, value, tie, span),
+                _ => continue,
+            };
+
+            let start = tempo_start + position.to_samples(bpm, sample_rate);
+            let Some(end) = value
+                .checked_mul(scale)
+                .and_then(|value| position.checked_add(value))
+            else {
+                errors.push(
+                    ArgumentParseError::new("Tune is too long or too finely divided")
+                        .at_span(*span),
+                );
+                break;
+            };
+            position = end;
+            let num_samples = tempo_start + position.to_samples(bpm, sample_rate) - start;
+
+            let frequencies = match pitches
+                .iter()
+                .map(|pitch| pitch.frequency(tuning))
+                .collect::<Result<Vec<f32>, String>>()
+            {
+                Ok(frequencies) => frequencies,
+                Err(e) => {
+                    errors.push(ArgumentParseError::new(&e).at_span(*span));
+                    continue;
+                }
+            };
+            match notes.last_mut() {
+                Some(last) if tied && last.frequencies == frequencies => {
+                    last.num_samples += num_samples;
+                    last.value = last.value.and_then(|last| last.checked_add(*value));
+                }
+                _ if num_samples == 0 => {}
+                _ if pitches.is_empty() => notes.push(Note {
+                    value: Some(*value),
+                    ..Note::rest(num_samples)
+                }),
+                _ => match Note::chord(frequencies, DEFAULT_AMPLITUDE, sample_rate, num_samples) {
+                    Ok(note) => notes.push(Note {
+                        pitches: pitches.clone(),
+                        value: Some(*value),
+                        ..note
+                    }),
+                    Err(e) => errors.push(ArgumentParseError::new(&e).at_span(*span)),
+                },
+            }
+            tied = *tie;
+        }
+        self.errors.extend(errors);
+        notes
+    }
+}
+
+/// Parse the first tune of an ABC source into notes.
+///
+/// The header fields `M:` (meter), `L:` (unit note length), `Q:` (tempo) and `K:` (key) are
+/// followed, both on lines of their own and inline, e.g. `[K:Dm]`, while other fields such
+/// as `X:` and `T:` are ignored. Without a `Q:` field, the tune is played at `tempo` quarter
+/// notes per minute. Accidentals apply to the rest of their bar, and notes are otherwise
+/// played in the key. Repeats `|: :|` and endings `[1 [2` are unfolded, and ties, chords,
+/// tuplets such as `(3` and broken rhythms such as `A>B` are supported. Decorations,
+/// annotations, slurs and grace notes are skipped.
+pub fn parse_abc(
+    source: &str,
+    tuning: &Tuning,
+    tempo: u32,
+    sample_rate: u32,
+) -> Result<Vec<Note>, ParseErrors> {
+    if tempo == 0 {
+        return Err(ParseErrors::new(
+            vec![ArgumentParseError::new("Tempo must be greater than zero")],
+            source,
+        ));
+    }
+
+    let mut parser = AbcParser::new(tempo);
+    parser.parse_tune(source);
+    let notes = parser.get_notes(tuning, sample_rate);
+    if parser.errors.is_empty() {
+        Ok(notes)
+    } else {
+        Err(ParseErrors::new(parser.errors, source))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEMPO: u32 = 120;
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn parse(source: &str) -> Result<Vec<Note>, ParseErrors> {
+        parse_abc(source, &Tuning::new(440.0), TEMPO, SAMPLE_RATE)
+    }
+
+    /// Pitch names and note values of the tune with `header` and `body`, where rests have
+    /// no pitch names.
+    fn notes(header: &str, body: &str) -> Vec<(String, NoteValue)> {
+        parse(&format!("X:1\n{header}\n{body}\n"))
+            .unwrap()
+            .into_iter()
+            .map(|note| {
+                let names: Vec<&str> = note.pitches.iter().map(|p| p.name.as_str()).collect();
+                (names.join(" "), note.value.unwrap())
+            })
+            .collect()
+    }
+
+    fn names(header: &str, body: &str) -> Vec<String> {
+        notes(header, body)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    fn values(header: &str, body: &str) -> Vec<NoteValue> {
+        notes(header, body)
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect()
+    }
+
+    #[test]
+    fn abc_sources() {
+        assert!(is_abc("X:1\nK:C\nCDE"));
+        assert!(is_abc("% tune\n\n  X:1\nK:C"));
+        assert!(!is_abc("C4:4 D4:4"));
+        assert!(!is_abc("T:Title\nX:1"));
+    }
+
+    #[test]
+    fn key_signatures() {
+        let key = |s| parse_key(s).unwrap();
+        assert_eq!(key("C"), [0; 7]);
+        assert_eq!(key("G"), [0, 0, 0, 1, 0, 0, 0]);
+        assert_eq!(key("Bb"), [0, 0, -1, 0, 0, 0, -1]);
+        assert_eq!(key("Em"), key("G"));
+        assert_eq!(key("Ador"), key("G"));
+        assert_eq!(key("D mix"), key("G"));
+        assert_eq!(key("F#"), [1, 1, 1, 1, 1, 1, 0]);
+        assert_eq!(key("Cb"), [-1; 7]);
+        assert_eq!(key("C# lyd"), [1, 1, 1, 2, 1, 1, 1]);
+        assert_eq!(key("D ^g _b"), [1, 0, 0, 1, 1, 0, -1]);
+        assert_eq!(key("none"), [0; 7]);
+        assert_eq!(key("G clef=bass"), key("G"));
+        assert!(parse_key("H").is_err());
+        assert!(parse_key("Gxyz").is_err());
+        assert!(parse_key("G f").is_err());
+
+        assert_eq!(names("K:D", "DFGc"), ["D4", "F#4", "G4", "C#5"]);
+        assert_eq!(names("K:Eb", "EAB"), ["Eb4", "Ab4", "Bb4"]);
+        assert_eq!(names("K:C", "F[K:G]F"), ["F4", "F#4"]);
+    }
+
+    #[test]
+    fn bar_accidentals() {
+        // Accidentals apply to the same note for the rest of the bar, but not to other octaves
+        assert_eq!(names("K:C", "^FFf|F"), ["F#4", "F#4", "F5", "F4"]);
+        assert_eq!(
+            names("K:G", "=FF^^G_A|F"),
+            ["F4", "F4", "G##4", "Ab4", "F#4"]
+        );
+        assert_eq!(names("K:C", "__B,B,|B,"), ["Bbb3", "Bbb3", "B3"]);
+        assert_eq!(names("K:C", "[^CE]C"), ["C#4 E4", "C#4"]);
+    }
+
+    #[test]
+    fn octaves() {
+        assert_eq!(
+            names("K:C", "C, C c c' c''"),
+            ["C3", "C4", "C5", "C6", "C7"]
+        );
+    }
+
+    #[test]
+    fn unit_note_lengths() {
+        let eighth = NoteValue::new(1, 8);
+        let sixteenth = NoteValue::new(1, 16);
+        // Without an L: field the unit depends on the meter, which is 4/4 by default
+        assert_eq!(values("K:C", "C"), [eighth]);
+        assert_eq!(values("M:3/4\nK:C", "C"), [eighth]);
+        assert_eq!(values("M:2/4\nK:C", "C"), [sixteenth]);
+        assert_eq!(values("M:C|\nK:C", "C"), [eighth]);
+        assert_eq!(values("M:none\nK:C", "C"), [eighth]);
+        assert_eq!(values("M:2/4\nL:1/4\nK:C", "C"), [NoteValue::new(1, 4)]);
+        assert_eq!(
+            values("L:1/4\nK:C", "C2 C/ C// C/4 C3/2 z2 C[L:1/8]C"),
+            [
+                NoteValue::new(1, 2),
+                eighth,
+                sixteenth,
+                sixteenth,
+                NoteValue::new(3, 8),
+                NoteValue::new(1, 2),
+                NoteValue::new(1, 4),
+                eighth,
+            ]
+        );
+        assert_eq!(values("M:3/4\nK:C", "Z2"), [NoteValue::new(3, 2)]);
+        assert_eq!(values("M:(2+3)/8\nK:C", "Z"), [NoteValue::new(5, 8)]);
+    }
+
+    #[test]
+    fn tempos() {
+        let samples = |header: &str| -> Vec<u128> {
+            parse(&format!("X:1\nL:1/4\n{header}\nK:C\nCC\n"))
+                .unwrap()
+                .iter()
+                .map(|note| note.num_samples)
+                .collect()
+        };
+        // Without a Q: field, the tune is played at the given tempo
+        assert_eq!(samples(""), [24000, 24000]);
+        assert_eq!(samples("Q:1/4=60"), [48000, 48000]);
+        assert_eq!(samples("Q:3/8=40"), [48000, 48000]);
+        assert_eq!(samples("Q:1/8 1/8=60"), [48000, 48000]);
+        assert_eq!(samples("Q:\"Allegro\" 1/2=120"), [12000, 12000]);
+        // A tempo without a beat counts unit note lengths
+        assert_eq!(samples("Q:240"), [12000, 12000]);
+
+        let notes = parse("X:1\nL:1/4\nK:C\nC[Q:1/4=60]C\n").unwrap();
+        assert_eq!(notes[0].num_samples, 24000);
+        assert_eq!(notes[1].num_samples, 48000);
+    }
+
+    #[test]
+    fn tuplets() {
+        let third = NoteValue::new(1, 12);
+        assert_eq!(
+            values("K:C", "(3CDE F"),
+            [third, third, third, NoteValue::new(1, 8)]
+        );
+        assert_eq!(
+            values("K:C", "(2CD"),
+            [NoteValue::new(3, 16), NoteValue::new(3, 16)]
+        );
+        assert_eq!(values("M:6/8\nK:C", "(5CDEFG").len(), 5);
+        assert_eq!(values("M:6/8\nK:C", "(5CDEFG")[0], NoteValue::new(3, 40));
+        assert_eq!(values("M:4/4\nK:C", "(5CDEFG")[0], NoteValue::new(1, 20));
+        assert_eq!(
+            values("K:C", "(3:2:2C2D C"),
+            [
+                NoteValue::new(1, 6),
+                NoteValue::new(1, 12),
+                NoteValue::new(1, 8)
+            ]
+        );
+    }
+
+    #[test]
+    fn broken_rhythms() {
+        let dotted = NoteValue::new(3, 16);
+        let short = NoteValue::new(1, 16);
+        assert_eq!(values("K:C", "A>B"), [dotted, short]);
+        assert_eq!(values("K:C", "A<B"), [short, dotted]);
+        assert_eq!(
+            values("K:C", "A>>B"),
+            [NoteValue::new(7, 32), NoteValue::new(1, 32)]
+        );
+        assert_eq!(values("K:C", "[CE]>z"), [dotted, short]);
+    }
+
+    #[test]
+    fn ties() {
+        // Tied notes of the same pitches are merged, while other ties are ignored
+        assert_eq!(
+            notes("K:C", "C2-C|D-E [CE]-[CE]"),
+            [
+                (String::from("C4"), NoteValue::new(3, 8)),
+                (String::from("D4"), NoteValue::new(1, 8)),
+                (String::from("E4"), NoteValue::new(1, 8)),
+                (String::from("C4 E4"), NoteValue::new(1, 4)),
+            ]
+        );
+        assert_eq!(values("K:C", "[C-E-]C"), [NoteValue::new(1, 8); 2]);
+        let notes = parse("X:1\nL:1/4\nK:C\nC-C\n").unwrap();
+        assert_eq!(notes[0].num_samples, 48000);
+    }
+
+    #[test]
+    fn repeats() {
+        assert_eq!(names("K:C", "C|:D:|E"), ["C4", "D4", "D4", "E4"]);
+        assert_eq!(names("K:C", "CD:|E"), ["C4", "D4", "C4", "D4", "E4"]);
+        assert_eq!(names("K:C", "|:C:|:D:|"), ["C4", "C4", "D4", "D4"]);
+        assert_eq!(names("K:C", "|:C:||:D:|"), ["C4", "C4", "D4", "D4"]);
+        // Without a |: the repeat goes back to the start of the tune, even across a section end
+        assert_eq!(names("K:C", "C||D:|"), ["C4", "D4", "C4", "D4"]);
+    }
+
+    #[test]
+    fn endings() {
+        assert_eq!(names("K:C", "|:C|1D:|2E|F"), ["C4", "D4", "C4", "E4", "F4"]);
+        assert_eq!(
+            names("K:C", "|:C[1D:|[2E|]F"),
+            ["C4", "D4", "C4", "E4", "F4"]
+        );
+    }
+
+    #[test]
+    fn skipped_elements() {
+        assert_eq!(
+            names(
+                "T:Title\nC:Composer\nK:C",
+                "\"Am\"!trill!C {g}D (E F) ~G .A % comment"
+            ),
+            ["C4", "D4", "E4", "F4", "G4", "A4"]
+        );
+        // A blank line ends the tune, and so does another tune
+        assert_eq!(names("K:C", "C\n\nD"), ["C4"]);
+        assert_eq!(names("K:C", "C\nX:2\nK:C\nD"), ["C4"]);
+    }
+
+    #[test]
+    fn error_spans() {
+        let spans = |source: &str| -> Vec<Option<Span>> {
+            parse(source)
+                .unwrap_err()
+                .errors()
+                .iter()
+                .map(|e| e.span())
+                .collect()
+        };
+        let source = "X:1\nK:C\nC # D\n";
+        assert_eq!(spans(source), [Some(Span::new(10, 11))]);
+        assert_eq!(spans("X:1\nK:H\nC\n"), [Some(Span::new(6, 7))]);
+        assert_eq!(spans("X:1\nM:  6-8\nK:C\nC\n"), [Some(Span::new(8, 11))]);
+        assert_eq!(spans("X:1\nK:C\nC [L:x] D\n"), [Some(Span::new(13, 14))]);
+        assert_eq!(spans("X:1\nK:C\nC ^ D\n"), [Some(Span::new(10, 12))]);
+        assert_eq!(
+            spans("X:1\nK:C\nC0 \"text\n"),
+            [Some(Span::new(9, 10)), Some(Span::new(11, 16))]
+        );
+        assert_eq!(spans("X:1\nK:C\n-C >D\n"), [Some(Span::new(8, 9)),]);
+        assert_eq!(spans("X:1\nK:C\nC [C E\n"), [Some(Span::new(10, 14))]);
+        assert_eq!(spans("X:1\nK:C\nC (1C\n"), [Some(Span::new(10, 12))]);
+        assert_eq!(spans("X:1\nM:none\nK:C\nZ2\n"), [Some(Span::new(15, 17))]);
+    }
+
+    #[test]
+    fn invalid_tunes() {
+        let sources = [
+            "X:1\nV:1\nK:C\nC",
+            "X:1\nL:0/8\nK:C\nC",
+            "X:1\nQ:1/4=0\nK:C\nC",
+            "X:1\nQ:fast\nK:C\nC",
+            "X:1\nK:C\nC>>>>>>>>>D",
+            "X:1\nK:C\n>C",
+            "X:1\nK:C\n[]",
+            "X:1\nK:C\n[C;]",
+            "X:1\nK:C\nC|1-99D",
+            "X:1\nK:C\nC : D",
+            "X:1\nK:C\nc''''''''''",
+        ];
+        for source in sources {
+            assert!(parse(source).is_err(), "{source:?} was parsed");
+        }
+        assert!(parse_abc("X:1\nK:C\nC", &Tuning::new(440.0), 0, SAMPLE_RATE).is_err());
+    }
+}
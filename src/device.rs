@@ -0,0 +1,256 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use cpal::traits::{DeviceTrait, HostTrait};
+use cpal::{
+    Device, FromSample, Host, SampleFormat, SampleRate, SizedSample, Stream, StreamConfig,
+    SupportedStreamConfig,
+};
+
+use crate::player::Player;
+
+/// Sample formats in order of preference. Formats that the player renders in natively or that
+/// convert without loss of precision are preferred.
+const SAMPLE_FORMATS: [SampleFormat; 10] = [
+    SampleFormat::F32,
+    SampleFormat::F64,
+    SampleFormat::I32,
+    SampleFormat::I16,
+    SampleFormat::U32,
+    SampleFormat::U16,
+    SampleFormat::I64,
+    SampleFormat::U64,
+    SampleFormat::I8,
+    SampleFormat::U8,
+];
+
+fn sample_format_rank(sample_format: SampleFormat) -> usize {
+    SAMPLE_FORMATS
+        .iter()
+        .position(|&format| format == sample_format)
+        .unwrap_or(SAMPLE_FORMATS.len())
+}
+
+/// Pick the output config of `device` that supports `sample_rate` with the most preferred
+/// sample format.
+pub fn get_device_config(
+    device: &Device,
+    sample_rate: u32,
+) -> Result<SupportedStreamConfig, String> {
+    let configs: Vec<_> = device
+        .supported_output_configs()
+        .map_err(|e| format!("Error while querying configs: {e}"))?
+        .collect();
+
+    configs
+        .iter()
+        .filter(|config| {
+            (config.min_sample_rate().0..=config.max_sample_rate().0).contains(&sample_rate)
+        })
+        .min_by_key(|config| sample_format_rank(config.sample_format()))
+        .map(|config| config.with_sample_rate(SampleRate(sample_rate)))
+        .ok_or_else(|| {
+            let supported: Vec<String> = configs
+                .iter()
+                .map(|config| {
+                    format!(
+                        "{}-{} Hz",
+                        config.min_sample_rate().0,
+                        config.max_sample_rate().0
+                    )
+                })
+                .collect();
+            format!(
+                "Device does not support a sample rate of {sample_rate} Hz. \
+                Supported sample rates are: {}",
+                supported.join(", ")
+            )
+        })
+}
+
+/// Build a stream playing `player` on `device`, converting the samples to the sample format of
+/// `config`. `done` is set once the player has finished.
+pub fn build_output_stream(
+    device: &Device,
+    config: &SupportedStreamConfig,
+    player: Player,
+    done: Arc<AtomicBool>,
+) -> Result<Stream, String> {
+    let stream_config = config.config();
+    match config.sample_format() {
+        SampleFormat::F32 => build_stream::<f32>(device, &stream_config, player, done),
+        SampleFormat::F64 => build_stream::<f64>(device, &stream_config, player, done),
+        SampleFormat::I8 => build_stream::<i8>(device, &stream_config, player, done),
+        SampleFormat::I16 => build_stream::<i16>(device, &stream_config, player, done),
+        SampleFormat::I32 => build_stream::<i32>(device, &stream_config, player, done),
+        SampleFormat::I64 => build_stream::<i64>(device, &stream_config, player, done),
+        SampleFormat::U8 => build_stream::<u8>(device, &stream_config, player, done),
+        SampleFormat::U16 => build_stream::<u16>(device, &stream_config, player, done),
+        SampleFormat::U32 => build_stream::<u32>(device, &stream_config, player, done),
+        SampleFormat::U64 => build_stream::<u64>(device, &stream_config, player, done),
+        sample_format => Err(format!("Unsupported sample format {sample_format}")),
+    }
+}
+
+fn build_stream<T>(
+    device: &Device,
+    config: &StreamConfig,
+    mut player: Player,
+    done: Arc<AtomicBool>,
+) -> Result<Stream, String>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let mut frame = vec![0f32; config.channels as usize];
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                for output_frame in data.chunks_mut(frame.len()) {
+                    if !player.write_frame(&mut frame) {
+                        done.store(true, Ordering::SeqCst);
+                    }
+                    for (output_sample, &sample) in output_frame.iter_mut().zip(&frame) {
+                        *output_sample = T::from_sample(sample);
+                    }
+                }
+            },
+            move |err| {
+                eprintln!("Output stream callback failed: {}", err);
+            },
+            None,
+        )
+        .map_err(|e| format!("Failed to build output stream: {e}"))
+}
+
+/// Find an output device of `host` from `query`, which is either the index of the device in
+/// [`HostTrait::output_devices`], its exact name, or a case insensitive part of its name that
+/// matches a single device.
+pub fn find_output_device(host: &Host, query: &str) -> Result<Device, String> {
+    let devices: Vec<(String, Device)> = host
+        .output_devices()
+        .map_err(|e| format!("Failed to get output devices: {e}"))?
+        .map(|device| (device.name().unwrap_or_default(), device))
+        .collect();
+    let candidates = |devices: &[&(String, Device)]| -> String {
+        devices
+            .iter()
+            .map(|(name, _)| format!("\n  {name}"))
+            .collect()
+    };
+
+    if let Ok(index) = query.parse::<usize>() {
+        let num_devices = devices.len();
+        return devices
+            .into_iter()
+            .nth(index)
+            .map(|(_, device)| device)
+            .ok_or_else(|| {
+                format!("No output device with index {index}, there are {num_devices} devices")
+            });
+    }
+
+    if let Some(position) = devices.iter().position(|(name, _)| name == query) {
+        return Ok(devices.into_iter().nth(position).unwrap().1);
+    }
+
+    let lowercase_query = query.to_lowercase();
+    let matches: Vec<&(String, Device)> = devices
+        .iter()
+        .filter(|(name, _)| name.to_lowercase().contains(&lowercase_query))
+        .collect();
+    match matches.as_slice() {
+        [(_, device)] => Ok(device.clone()),
+        [] if devices.is_empty() => Err(format!(
+            "Failed to find device '{query}', since there are no output devices"
+        )),
+        [] => Err(format!(
+            "Failed to find device '{query}'. Available output devices are:{}",
+            candidates(&devices.iter().collect::<Vec<_>>())
+        )),
+        _ => Err(format!(
+            "Device '{query}' is ambiguous, it matches:{}",
+            candidates(&matches)
+        )),
+    }
+}
+
+/// Find an output device from `selector`, which is a query for [`find_output_device`] on the
+/// default host, or on another host if prefixed with its name and a colon, e.g. `jack:0`.
+pub fn select_output_device(selector: &str) -> Result<Device, String> {
+    let host_query = selector.split_once(':').and_then(|(name, query)| {
+        cpal::available_hosts()
+            .into_iter()
+            .find(|host_id| host_id.name().eq_ignore_ascii_case(name))
+            .map(|host_id| (host_id, query))
+    });
+    match host_query {
+        Some((host_id, query)) => {
+            let host = cpal::host_from_id(host_id)
+                .map_err(|e| format!("Host {} is unavailable: {e}", host_id.name()))?;
+            find_output_device(&host, query)
+        }
+        None => find_output_device(&cpal::default_host(), selector),
+    }
+}
+
+/// Describe every host available on the platform, with its output devices and their
+/// supported configs. Devices are numbered by their index, prefixed with the name of their host
+/// unless it is the default host, so that they can be selected by [`select_output_device`].
+pub fn describe_devices() -> String {
+    let default_host = cpal::default_host().id();
+    let mut description = String::new();
+
+    for host_id in cpal::available_hosts() {
+        let (default_marker, index_prefix) = if host_id == default_host {
+            (" (default)", String::new())
+        } else {
+            ("", format!("{}:", host_id.name().to_lowercase()))
+        };
+        description += &format!("Host: {}{default_marker}\n", host_id.name());
+
+        let host = match cpal::host_from_id(host_id) {
+            Ok(host) => host,
+            Err(e) => {
+                description += &format!("  Unavailable: {e}\n");
+                continue;
+            }
+        };
+        let default_device = host
+            .default_output_device()
+            .and_then(|device| device.name().ok());
+        let devices = match host.output_devices() {
+            Ok(devices) => devices,
+            Err(e) => {
+                description += &format!("  Failed to get output devices: {e}\n");
+                continue;
+            }
+        };
+
+        for (index, device) in devices.enumerate() {
+            let name = device.name().unwrap_or_else(|_| String::from("<unknown>"));
+            let default_marker = if Some(&name) == default_device.as_ref() {
+                " (default)"
+            } else {
+                ""
+            };
+            description += &format!("  {index_prefix}{index}: {name}{default_marker}\n");
+
+            match device.supported_output_configs() {
+                Ok(configs) => {
+                    for config in configs {
+                        description += &format!(
+                            "       {} channels, {}, {}-{} Hz\n",
+                            config.channels(),
+                            config.sample_format(),
+                            config.min_sample_rate().0,
+                            config.max_sample_rate().0
+                        );
+                    }
+                }
+                Err(e) => description += &format!("       Failed to query configs: {e}\n"),
+            }
+        }
+    }
+    description
+}
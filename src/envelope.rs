@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+/// Attack, decay, sustain and release envelope applied to the amplitude of every note.
+///
+/// A note rises from silence to full amplitude over `attack`, falls to `sustain` over `decay`
+/// and stays there until the note ends. It then fades to silence over `release`, while the
+/// next note of the voice is already playing.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Envelope {
+    pub attack: Duration,
+    pub decay: Duration,
+    /// Level relative to the amplitude of the note, from 0 to 1.
+    pub sustain: f32,
+    pub release: Duration,
+}
+
+impl Default for Envelope {
+    /// A short attack and release, which is just long enough to avoid clicks at note
+    /// boundaries.
+    fn default() -> Self {
+        Envelope {
+            attack: Duration::from_millis(5),
+            decay: Duration::ZERO,
+            sustain: 1.0,
+            release: Duration::from_millis(10),
+        }
+    }
+}
+
+impl Envelope {
+    pub fn new(attack: Duration, decay: Duration, sustain: f32, release: Duration) -> Self {
+        Envelope {
+            attack,
+            decay,
+            sustain,
+            release,
+        }
+    }
+
+    /// Gain of a note that has been held for `elapsed` seconds.
+    pub fn held_gain(&self, elapsed: f32) -> f32 {
+        let attack = self.attack.as_secs_f32();
+        let decay = self.decay.as_secs_f32();
+        if elapsed < attack {
+            elapsed / attack
+        } else if elapsed < attack + decay {
+            1.0 - (1.0 - self.sustain) * (elapsed - attack) / decay
+        } else {
+            self.sustain
+        }
+    }
+
+    /// Gain of a note released `elapsed` seconds ago at gain `level`, or `None` once the
+    /// release has finished.
+    pub fn released_gain(&self, level: f32, elapsed: f32) -> Option<f32> {
+        let release = self.release.as_secs_f32();
+        if elapsed < release {
+            Some(level * (1.0 - elapsed / release))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn envelope() -> Envelope {
+        Envelope::new(
+            Duration::from_millis(10),
+            Duration::from_millis(20),
+            0.5,
+            Duration::from_millis(40),
+        )
+    }
+
+    #[test]
+    fn held_gain() {
+        let envelope = envelope();
+        assert_eq!(envelope.held_gain(0.0), 0.0);
+        assert_eq!(envelope.held_gain(0.005), 0.5);
+        assert!((envelope.held_gain(0.02) - 0.75).abs() < 1e-6);
+        assert_eq!(envelope.held_gain(0.03), 0.5);
+        assert_eq!(envelope.held_gain(10.0), 0.5);
+
+        let instant = Envelope::new(Duration::ZERO, Duration::ZERO, 0.8, Duration::ZERO);
+        assert_eq!(instant.held_gain(0.0), 0.8);
+    }
+
+    #[test]
+    fn released_gain() {
+        let envelope = envelope();
+        assert_eq!(envelope.released_gain(0.5, 0.0), Some(0.5));
+        assert_eq!(envelope.released_gain(0.5, 0.01), Some(0.375));
+        assert_eq!(envelope.released_gain(0.5, 0.04), None);
+        assert_eq!(Envelope::default().released_gain(1.0, 0.0), Some(1.0));
+        let no_release = Envelope {
+            release: Duration::ZERO,
+            ..Envelope::default()
+        };
+        assert_eq!(no_release.released_gain(1.0, 0.0), None);
+    }
+}
@@ -0,0 +1,84 @@
+/// Byte range of a token or part of a token within the source of a sequence.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    /// Span of `len` bytes starting `offset` bytes into this span.
+    pub fn sub(&self, offset: usize, len: usize) -> Span {
+        Span::new(self.start + offset, self.start + offset + len)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    /// A note, rest, tuplet ratio or voice option, e.g. `C#4:8.`, `r:4`, `3:2` or `@square`.
+    Word,
+    /// `{`, opening a tuplet group.
+    TupletOpen,
+    /// `}`, closing a tuplet group.
+    TupletClose,
+    /// `[`, opening a chord.
+    ChordOpen,
+    /// `]`, closing a chord.
+    ChordClose,
+    /// `~`, tying two notes together.
+    Tie,
+    /// `|`, separating two voices.
+    VoiceSeparator,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    pub span: Span,
+}
+
+fn punctuation(c: char) -> Option<TokenKind> {
+    match c {
+        '{' => Some(TokenKind::TupletOpen),
+        '}' => Some(TokenKind::TupletClose),
+        '[' => Some(TokenKind::ChordOpen),
+        ']' => Some(TokenKind::ChordClose),
+        '~' => Some(TokenKind::Tie),
+        '|' => Some(TokenKind::VoiceSeparator),
+        _ => None,
+    }
+}
+
+/// Split the source of a sequence into tokens. Punctuation is always a token of its own,
+/// while any other run of characters up to whitespace or punctuation is a word.
+pub fn tokenize(source: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let kind = match punctuation(c) {
+            Some(kind) => kind,
+            None => {
+                while chars
+                    .next_if(|&(_, c)| !c.is_whitespace() && punctuation(c).is_none())
+                    .is_some()
+                {}
+                TokenKind::Word
+            }
+        };
+        let end = chars.peek().map_or(source.len(), |&(end, _)| end);
+        tokens.push(Token {
+            kind,
+            text: &source[start..end],
+            span: Span::new(start, end),
+        });
+    }
+    tokens
+}
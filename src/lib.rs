@@ -0,0 +1,52 @@
+//! Parse note sequences and render them to audio samples.
+//!
+//! A sequence is written as a list of `<pitch>:<note value>` tokens, e.g. `C4:4 E4:4 G4:2`,
+//! with independent voices separated by `|`.
+//! [`Sequence::parse`] turns such tokens into [`Note`]s, and a [`Player`] renders a
+//! [`Sequence`] into a stream of mono `f32` samples that can be fed to any audio sink, or
+//! written to a WAV file with [`write_wav`]. A sequence can also be exported as a Standard
+//! MIDI File with [`write_midi`], and Standard MIDI Files can be played with
+//! [`Sequence::from_midi`]. Tunes in ABC notation are parsed with [`parse_abc`], and MusicXML
+//! scores are read with [`read_musicxml`]. Sequences are engraved by exporting them as
+//! MusicXML with [`write_musicxml`], or as LilyPond with [`write_lilypond`]. Note names are
+//! tuned by a [`Tuning`], in equal temperament, in one of the historical [`Temperament`]s or
+//! to a [`Scala`] scale.
+
+pub mod abc;
+pub mod device;
+pub mod envelope;
+pub mod lexer;
+pub mod lilypond;
+pub mod midi;
+pub mod mixer;
+pub mod musicxml;
+mod notation;
+pub mod note;
+pub mod parser;
+pub mod player;
+pub mod scala;
+pub mod sequence;
+pub mod tuning;
+pub mod value;
+pub mod wav;
+pub mod waveform;
+
+pub use abc::{is_abc, parse_abc};
+pub use envelope::Envelope;
+pub use lilypond::write_lilypond;
+pub use midi::{is_midi, read_midi, write_midi, MidiFormat};
+pub use musicxml::{is_musicxml, read_musicxml, write_musicxml};
+pub use note::{get_frequency_from_note, Note, Pitch};
+pub use parser::{
+    parse_duration, parse_note_value, parse_notes, parse_voices, strip_comments, ArgumentParseError,
+};
+pub use player::Player;
+pub use scala::{KeyboardMapping, Scala, Scale};
+pub use sequence::{Sequence, Voice};
+pub use tuning::{Temperament, Tuning};
+pub use value::NoteValue;
+pub use wav::{write_wav, BitDepth};
+pub use waveform::{Oscillator, Waveform};
+
+/// Octave in which the reference pitch (A) of the tuning is located.
+pub const REFERENCE_OCTAVE: i32 = 4;
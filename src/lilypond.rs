@@ -0,0 +1,176 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::notation::{is_bass, measure_length, notate_voice, NotatedNote};
+use crate::note::Pitch;
+use crate::sequence::Sequence;
+use crate::value::NoteValue;
+
+/// Octave of the pitches LilyPond writes without octave marks, e.g. `c` is C3.
+const UNMARKED_OCTAVE: i32 = 3;
+
+/// LilyPond name of a pitch as it was written, e.g. `Bb5` is `bes''`. Deviations in cents are
+/// rounded to quarter tones, which are written with the half sharps `ih` and `isih` and half
+/// flats `eh` and `eseh`, while the cents left over are left out.
+fn get_pitch_name(pitch: &Pitch) -> String {
+    let (letter, accidental, octave) = pitch.spelling();
+    let quarter_tones = 2 * accidental + (pitch.cents / 50.0).round() as i32;
+    let accidental = match quarter_tones {
+        1 => String::from("ih"),
+        3 => String::from("isih"),
+        -1 => String::from("eh"),
+        -3 => String::from("eseh"),
+        q if q < 0 => "es".repeat((-q / 2) as usize),
+        q => "is".repeat((q / 2) as usize),
+    };
+    let mark = match octave < UNMARKED_OCTAVE {
+        true => ",",
+        false => "'",
+    };
+    format!(
+        "{}{accidental}{}",
+        letter.to_ascii_lowercase(),
+        mark.repeat((octave - UNMARKED_OCTAVE).unsigned_abs() as usize)
+    )
+}
+
+/// LilyPond notation of a note, chord or rest, e.g. `<c' e'>4.~`.
+fn get_note(note: &NotatedNote) -> String {
+    let pitches = match note.pitches.as_slice() {
+        [] => String::from("r"),
+        [pitch] => get_pitch_name(pitch),
+        pitches => format!(
+            "<{}>",
+            pitches
+                .iter()
+                .map(get_pitch_name)
+                .collect::<Vec<_>>()
+                .join(" ")
+        ),
+    };
+    format!(
+        "{pitches}{}{}{}",
+        1u32 << note.value.divisor_log,
+        ".".repeat(note.value.dots as usize),
+        if note.tie { "~" } else { "" }
+    )
+}
+
+/// Write a measure, grouping consecutive notes of the same tuplet. It ends with a bar check
+/// unless it is shorter than a full measure, which only the last measure of a voice can be.
+fn write_measure(ly: &mut String, measure: &[NotatedNote]) {
+    let mut tuplet = None;
+    let mut items = Vec::new();
+    for note in measure {
+        if note.value.tuplet != tuplet {
+            if tuplet.is_some() {
+                items.push(String::from("}"));
+            }
+            if let Some((actual, normal)) = note.value.tuplet {
+                items.push(format!("\\tuplet {actual}/{normal} {{"));
+            }
+            tuplet = note.value.tuplet;
+        }
+        items.push(get_note(note));
+    }
+    if tuplet.is_some() {
+        items.push(String::from("}"));
+    }
+    let length = measure.iter().try_fold(NoteValue::zero(), |length, note| {
+        length.checked_add(note.value.value)
+    });
+    if length == Some(measure_length()) {
+        items.push(String::from("|"));
+    }
+    let _ = writeln!(ly, "      {}", items.join(" "));
+}
+
+/// Write `sequence` to a LilyPond file with one staff per voice in 4/4 at `tempo`, engraved
+/// as a score and rendered as MIDI. Pitches and note values are written like in
+/// [`write_musicxml`](crate::write_musicxml).
+pub fn write_lilypond(path: &Path, sequence: &Sequence, tempo: u32) -> Result<(), String> {
+    let mut ly = String::from("\\version \"2.24.0\"\n\n\\score {\n  <<\n");
+    for (i, voice) in sequence.voices().iter().enumerate() {
+        let measures = notate_voice(voice, tempo, sequence.sample_rate())?;
+        let clef = match is_bass(&measures) {
+            true => "bass",
+            false => "treble",
+        };
+        let _ = writeln!(
+            ly,
+            "    \\new Staff \\with {{ instrumentName = \"Voice {}\" }} {{\n      \
+            \\clef {clef}\n      \\time 4/4",
+            i + 1
+        );
+        if i == 0 {
+            let _ = writeln!(ly, "      \\tempo 4 = {tempo}");
+        }
+        for measure in &measures {
+            write_measure(&mut ly, measure);
+        }
+        ly.push_str("    }\n");
+    }
+    ly.push_str("  >>\n  \\layout { }\n  \\midi { }\n}\n");
+
+    fs::write(path, ly)
+        .map_err(|e| format!("Failed to write LilyPond file '{}': {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_voices;
+    use crate::tuning::Tuning;
+
+    const TEMPO: u32 = 120;
+    const SAMPLE_RATE: u32 = 48000;
+
+    /// Measures LilyPond writes for the sequence in `source`.
+    fn measures(source: &str, name: &str) -> Vec<String> {
+        let voices = parse_voices(
+            &[source.to_string()],
+            &Tuning::new(440.0),
+            TEMPO,
+            SAMPLE_RATE,
+        )
+        .unwrap();
+        let path = std::env::temp_dir().join(format!("noteseq-{}-{name}.ly", std::process::id()));
+        write_lilypond(&path, &Sequence::with_voices(voices, SAMPLE_RATE), TEMPO).unwrap();
+        let ly = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        ly.lines()
+            .skip_while(|line| !line.contains("\\tempo"))
+            .skip(1)
+            .take_while(|line| !line.contains('}') || line.contains("\\tuplet"))
+            .map(|line| line.trim().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn pitch_names() {
+        let name = |semitones, cents| {
+            get_pitch_name(&Pitch {
+                name: String::new(),
+                semitones,
+                cents,
+            })
+        };
+        assert_eq!(name(0, 0.0), "a'");
+        assert_eq!(name(-12, 0.0), "a");
+        assert_eq!(name(-24, 0.0), "a,");
+        assert_eq!(name(-33, 0.0), "c,");
+        assert_eq!(name(0, 50.0), "aih'");
+        assert_eq!(name(0, -50.0), "aeh'");
+    }
+
+    #[test]
+    fn bar_checks() {
+        assert_eq!(measures("C4:1 D4:2", "full"), ["c'1 |", "d'2"]);
+        assert_eq!(measures("C4:2 D4:4", "incomplete"), ["c'2 d'4"]);
+        assert_eq!(
+            measures("C4:1 D4:2 E4:8t", "tuplet"),
+            ["c'1 |", "d'2 \\tuplet 3/2 { e'8 }"]
+        );
+    }
+}
@@ -1,345 +1,352 @@
 use clap::Parser;
-use core::panic;
-use cpal::{
-    traits::{DeviceTrait, HostTrait, StreamTrait},
-    Device, SampleRate, StreamConfig,
+use cpal::traits::{HostTrait, StreamTrait};
+use noteseq::device::{
+    build_output_stream, describe_devices, get_device_config, select_output_device,
+};
+use noteseq::{
+    is_abc, is_midi, is_musicxml, parse_abc, parse_voices, read_midi, read_musicxml,
+    strip_comments, write_lilypond, write_midi, write_musicxml, write_wav, BitDepth, Envelope,
+    KeyboardMapping, MidiFormat, Pitch, Player, Scala, Scale, Sequence, Temperament, Tuning, Voice,
+    Waveform,
 };
-use regex::Regex;
 use std::error::Error;
-use std::{f32::consts::PI, sync::Arc, time::Duration};
-use std::{
-    fmt,
-    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
 };
-
-const REFERENCE_OCTAVE: i32 = 4;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
     /// Note sequence to play. Format of notes is <pitch>:<note value>. The pitch part is on the
-    /// format <note name><accidentals><octave number>. Note name is a case insensitive letter
-    /// from A-G. Accidentals are optional and can be any number of '#' and 'b' symbols. Octave
-    /// number is a single number from 0-9. The note value part of the note is any number that is
-    /// a power of two, i.e. 1, 2, 4, 8, etc. This number represents the fraction of a whole note,
-    /// where the provided number is the divisor, e.g. 8 represents an eight note (1/8).
-    #[arg(required = true)]
+    /// format <note name><accidentals><octave number>. Note name is a case insensitive letter from
+    /// A-G. Accidentals are optional and can be any number of '#' (sharp), 'b' (flat), 'x' (double
+    /// sharp), '+' (quarter-tone sharp) and 'd' (quarter-tone flat) symbols. Octave number is a
+    /// number from -1 to 10, and can be followed by an offset in cents, e.g. A4+15c. A pitch can
+    /// also be a frequency in Hz, e.g. 440hz:4, or a MIDI note number from 0-127, e.g. m60:4.
+    /// Pitches must lie within the audible range of 8-20000 Hz. The note value part of the note is
+    /// any number that is a power of two, i.e. 1, 2, 4, 8, etc. This number represents the fraction
+    /// of a whole note, where the provided number is the divisor, e.g. 8 represents an eight note
+    /// (1/8). The note value can be followed by one or more dots, e.g. 4. is a dotted quarter note,
+    /// and by a 't' to make it a triplet, e.g. 8t. A pitch of 'r' or 'R' is a rest, e.g. r:4 is a
+    /// quarter rest. Notes can be grouped into tuplets with {n:m ...}, which plays n notes in the
+    /// time of m, e.g. {3:2 C:8 D:8 E:8}. Notes of the same pitch are tied with '~', e.g.
+    /// C4:2~C4:8, or by adding note values with '+', e.g. C4:2+8. Pitches enclosed in brackets are
+    /// played together as a chord, e.g. [C4 E4 G4]:2. Independent voices are separated by '|', and
+    /// a voice can start with @<amplitude> to set its volume, @<waveform> to set its waveform and
+    /// @pan:<position> to set its stereo position from -1 (left) to 1 (right), e.g. @0.5 @square
+    /// @pan:-0.5 C4:4 E4:4 | @0.3 C3:2. A single note sets its waveform with a @<waveform> suffix
+    /// and its stereo position with a @pan:<position> suffix, e.g. C4:4@saw@pan:0.5. With a Scala
+    /// scale, the pitch can also be a scale degree counted from the middle key of the keyboard
+    /// mapping, e.g. s7:4.
+    /// A sequence of '-' reads the sequence from stdin.
+    #[arg(required_unless_present_any = ["voice", "file", "list_devices"])]
     sequence: Vec<String>,
 
+    /// Read the sequence from a file, or from stdin if the path is '-'. The file is in the same
+    /// format as the sequence argument, can span several lines and can contain comments
+    /// starting with '//'. The file can also be a tune in ABC notation, starting with an X:
+    /// field, an uncompressed MusicXML score or a Standard MIDI File, which is played at its
+    /// own tempo.
+    #[arg(long)]
+    file: Option<PathBuf>,
+
+    /// Track of the MIDI file to play, numbered from 0. Can be given multiple times, and
+    /// defaults to every track.
+    #[arg(long, requires = "file")]
+    track: Vec<usize>,
+
+    /// Channel of the MIDI file to play, from 1 to 16. Can be given multiple times, and
+    /// defaults to every channel.
+    #[arg(long, requires = "file", value_parser = clap::value_parser!(u8).range(1..=16))]
+    channel: Vec<u8>,
+
+    /// Additional voice to play along with the sequence, in the same format as the sequence.
+    /// Can be given multiple times.
+    #[arg(long)]
+    voice: Vec<String>,
+
     /// Hold last note of sequence until stopped by the user
-    #[arg(short, long)]
+    #[arg(short, long, conflicts_with_all = ["output", "midi", "musicxml", "lilypond"])]
     fermata: bool,
 
     /// Tempo for note sequence
-    #[arg(short, long, default_value_t = 120)]
+    #[arg(short, long, default_value_t = 120, value_parser = clap::value_parser!(u32).range(1..))]
     tempo: u32,
 
     /// Reference pitch for note tuning
     #[arg(long, default_value_t = 440.0)]
     tuning: f32,
 
-    /// Device to play playback from
+    /// Temperament of notes: equal, just, pythagorean, meantone, werckmeister or kirnberger
+    #[arg(long, default_value_t = Temperament::Equal, conflicts_with = "scl")]
+    temperament: Temperament,
+
+    /// Note name of the tonic that the temperament is built on, e.g. Eb
+    #[arg(long, default_value = "C", value_parser = parse_tonic, conflicts_with = "scl")]
+    tonic: i32,
+
+    /// Tune notes to the scale of a Scala .scl file. Without a keyboard mapping, middle C is
+    /// the first degree of the scale and A4 is tuned to the reference pitch
+    #[arg(long)]
+    scl: Option<PathBuf>,
+
+    /// Map MIDI keys to the degrees of the Scala scale with a Scala .kbm file, which sets its
+    /// own reference pitch
+    #[arg(long, requires = "scl", conflicts_with = "tuning")]
+    kbm: Option<PathBuf>,
+
+    /// Waveform of notes: sine, square, sawtooth, triangle, pulse:<duty cycle> or noise
+    #[arg(short, long, default_value_t = Waveform::Sine)]
+    waveform: Waveform,
+
+    /// Attack time of every note in milliseconds
+    #[arg(long, default_value = "5", value_parser = parse_millis)]
+    attack: Duration,
+
+    /// Decay time of every note in milliseconds
+    #[arg(long, default_value = "0", value_parser = parse_millis)]
+    decay: Duration,
+
+    /// Sustain level of every note relative to its amplitude, from 0 to 1
+    #[arg(long, default_value_t = 1.0, value_parser = parse_sustain)]
+    sustain: f32,
+
+    /// Release time of every note in milliseconds, which overlaps the following note
+    #[arg(long, default_value = "10", value_parser = parse_millis)]
+    release: Duration,
+
+    /// Device to play playback from, given by its index or (part of) its name as shown by
+    /// --list-devices. Devices of other hosts than the default are prefixed with the name of the
+    /// host, e.g. jack:0
     #[arg(short, long)]
     device: Option<String>,
 
+    /// List the output devices of every audio host and their supported configs
+    #[arg(long, exclusive = true)]
+    list_devices: bool,
+
     /// Sample rate of playback
     #[arg(short, long, default_value_t = 48000)]
     sample_rate: u32,
-}
 
-#[derive(Copy, Clone)]
-struct Note {
-    frequency: f32,
-    amplitude: f32,
-    num_samples: u128,
+    /// Render the sequence to a WAV file instead of playing it on a device
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Number of channels of the rendered WAV file, where 2 or more gives a stereo mix
+    #[arg(short, long, default_value_t = 1, requires = "output")]
+    channels: u16,
+
+    /// Bit depth of the rendered WAV file: 16, 24, 32 or 32f (32-bit float)
+    #[arg(short, long, default_value_t = BitDepth::Int16, requires = "output")]
+    bit_depth: BitDepth,
+
+    /// Export the sequence to a Standard MIDI File instead of playing it on a device
+    #[arg(short, long)]
+    midi: Option<PathBuf>,
+
+    /// Type of the exported MIDI file: 0 for a single track, or 1 for a track per voice
+    #[arg(long, default_value_t = MidiFormat::MultiTrack, requires = "midi")]
+    midi_type: MidiFormat,
+
+    /// Export the sequence to a MusicXML score instead of playing it on a device
+    #[arg(long)]
+    musicxml: Option<PathBuf>,
+
+    /// Export the sequence to a LilyPond file instead of playing it on a device
+    #[arg(long)]
+    lilypond: Option<PathBuf>,
 }
 
-impl Note {
-    fn new(
-        frequency: f32,
-        amplitude: f32,
-        sample_rate: u32,
-        duration: Duration,
-    ) -> Result<Self, String> {
-        if frequency > sample_rate as f32 / 2.0 {
-            Err(String::from(format!(
-                "Cannot create note of frequency {frequency} Hz \
-                when the sample rate is {sample_rate} Hz, \
-                since it exceeds the Nyquist frequency of {nyquist} Hz.",
-                nyquist = sample_rate / 2
-            )))
-        } else {
-            Ok(Note {
-                frequency,
-                amplitude,
-                // Order of operations is important here to avoid truncation
-                num_samples: sample_rate as u128 * duration.as_millis() / 1000,
-            })
-        }
+fn parse_sustain(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(sustain) if (0.0..=1.0).contains(&sustain) => Ok(sustain),
+        _ => Err(format!("Sustain level '{s}' must be a number from 0 to 1")),
     }
 }
 
-fn get_device_config(device: &Device, sample_rate: u32) -> StreamConfig {
-    device
-        .supported_output_configs()
-        .expect("Error while querying configs")
-        .next()
-        .expect("No supported config")
-        .with_sample_rate(SampleRate(sample_rate))
-        .config()
+/// Pitch class of the tonic, in semitones above A.
+fn parse_tonic(s: &str) -> Result<i32, String> {
+    Ok(s.parse::<Pitch>()?.semitones.rem_euclid(12))
 }
 
-fn get_frequency_from_note(note_name: &str, tuning: f32) -> Result<f32, String> {
-    fn count_chars(string: &str, c: char) -> i32 {
-        string
-            .chars()
-            .filter(|x| *x == c)
-            .count()
-            .try_into()
-            .unwrap()
-    }
-    let re = Regex::new(r"^(?P<note>[a-gA-G])(?P<accidental>(#|b)*)(?P<octave>[0-9]?)$").unwrap();
-    let captures = match re.captures(note_name) {
-        Some(captures) => captures,
-        None => {
-            return Err(format!(
-                "Invalid note: {note_name}. Must be letter from A-G (case insensitive), \
-            optionally followed by accidental # or b and an octave number 0-9. E.g. C#4."
-            ))
-        }
-    };
-
-    let note = captures.name("note").unwrap().as_str();
-    let semitone_offset = match captures.name("accidental").unwrap().as_str() {
-        "" => 0,
-        accidental => {
-            let mut offset: i32 = 0;
-            offset += count_chars(accidental, '#');
-            offset -= count_chars(accidental, 'b');
-            offset
-        }
-    };
+/// Read and parse a Scala file of type `T`, described as `kind` in errors.
+fn read_scala_file<T: FromStr<Err = String>>(path: &Path, kind: &str) -> Result<T, String> {
+    fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {kind} '{}': {e}", path.display()))?
+        .parse::<T>()
+        .map_err(|e| format!("Invalid {kind} '{}': {e}", path.display()))
+}
 
-    let octave_num = match captures.name("octave").unwrap().as_str() {
-        "" => REFERENCE_OCTAVE,
-        octave => octave
-            .parse::<i32>()
-            .expect(format!("Failed to parse '{octave}' into octave number").as_str()),
+fn get_tuning(cli: &Cli) -> Result<Tuning, String> {
+    let Some(scl) = &cli.scl else {
+        return Ok(Tuning::with_temperament(
+            cli.tuning,
+            cli.temperament,
+            cli.tonic,
+        ));
     };
-
-    let mut semitone_distance: i32 = match note.to_uppercase().as_str() {
-        "C" => -9,
-        "D" => -7,
-        "E" => -5,
-        "F" => -4,
-        "G" => -2,
-        "A" => 0,
-        "B" => 2,
-        unknown => return Err(format!("Unknown note: {unknown}")),
+    let scale = read_scala_file::<Scale>(scl, "Scala scale")?;
+    let mapping = match &cli.kbm {
+        Some(kbm) => read_scala_file::<KeyboardMapping>(kbm, "keyboard mapping")?,
+        None => KeyboardMapping::linear(cli.tuning as f64),
     };
+    Ok(Tuning::with_scala(Scala::new(scale, mapping)?))
+}
 
-    semitone_distance += 12 * (octave_num - REFERENCE_OCTAVE) + semitone_offset;
-    Ok(2f32.powf(semitone_distance as f32 / 12.0) * tuning)
+fn parse_millis(s: &str) -> Result<Duration, String> {
+    s.parse::<f32>()
+        .ok()
+        .filter(|ms| *ms >= 0.0)
+        .and_then(|ms| Duration::try_from_secs_f32(ms / 1000.0).ok())
+        .ok_or_else(|| format!("Time '{s}' must be a non-negative number of milliseconds"))
 }
 
-struct Player {
-    pos: std::vec::IntoIter<Note>,
-    sample_rate: u32,
-    sample_num: u32,
-    current_note: Option<Note>,
+fn get_envelope(cli: &Cli) -> Envelope {
+    Envelope::new(cli.attack, cli.decay, cli.sustain, cli.release)
 }
 
-impl Player {
-    fn new(notes: Vec<Note>, sample_rate: u32) -> Self {
-        let mut pos = notes.clone().into_iter();
-        let current_note = match pos.next() {
-            Some(n) => n,
-            None => panic!(""),
-        };
-
-        Player {
-            pos,
-            sample_rate,
-            sample_num: 0,
-            current_note: Some(current_note),
-        }
+/// Read the contents of `path`, or of stdin if the path is '-'.
+fn read_file(path: &Path) -> Result<Vec<u8>, String> {
+    if path == Path::new("-") {
+        let mut data = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut data)
+            .map_err(|e| format!("Failed to read sequence from stdin: {e}"))?;
+        Ok(data)
+    } else {
+        fs::read(path)
+            .map_err(|e| format!("Failed to read sequence file '{}': {e}", path.display()))
     }
+}
 
-    fn next_note(&mut self) -> Option<Note> {
-        self.pos.next()
-    }
+/// Read a sequence source from `path`, or from stdin if the path is '-', without comments.
+fn read_source(path: &Path) -> Result<String, String> {
+    let source = String::from_utf8(read_file(path)?)
+        .map_err(|_| format!("Sequence file '{}' is not valid UTF-8", path.display()))?;
+    Ok(strip_comments(&source))
+}
 
-    fn next_note_val(&mut self) -> Option<Note> {
-        match self.current_note {
-            Some(current_note) => {
-                if current_note.num_samples != 0 {
-                    let current_sample_num = self.sample_num;
-                    self.sample_num += 1;
-
-                    if (current_sample_num as u128) >= current_note.num_samples {
-                        self.sample_num = 0;
-                        self.current_note = self.next_note();
-                    }
-                }
-                Some(current_note)
-            }
-            None => None,
-        }
+/// Combine the positional sequence and every --voice into one list of tokens, where each
+/// --voice becomes a separate part.
+fn get_sequence_tokens(cli: &Cli) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    if cli.sequence == ["-"] {
+        tokens.push(read_source(Path::new("-"))?);
+    } else {
+        tokens.extend(cli.sequence.iter().cloned());
     }
+    for voice in &cli.voice {
+        tokens.push(String::from("|"));
+        tokens.push(voice.clone());
+    }
+    Ok(tokens)
+}
 
-    fn get_next_sample(&mut self) -> Option<f32> {
-        static POS: AtomicU32 = AtomicU32::new(0);
-
-        match self.next_note_val() {
-            Some(n) => {
-                let t = POS.fetch_add(1, Ordering::SeqCst) as f32 / self.sample_rate as f32;
-                Some((2.0 * PI * n.frequency * t).sin() * n.amplitude)
+/// Read the sequence file, which is either a sequence source, an ABC tune, a MusicXML score
+/// or a MIDI file, and parse it together with the positional sequence and every --voice.
+fn get_sequence(cli: &Cli, sample_rate: u32) -> Result<Sequence, Box<dyn Error>> {
+    let mut voices = Vec::new();
+    let mut tokens = Vec::new();
+    let mut midi = false;
+    let tuning = get_tuning(cli)?;
+    if let Some(file) = &cli.file {
+        let data = read_file(file)?;
+        if is_midi(&data) {
+            midi = true;
+            voices = read_midi(&data, &cli.track, &cli.channel, &tuning, sample_rate)?;
+        } else {
+            let source = String::from_utf8(data)
+                .map_err(|_| format!("Sequence file '{}' is not valid UTF-8", file.display()))?;
+            if is_abc(&source) {
+                let notes = parse_abc(&source, &tuning, cli.tempo, sample_rate)?;
+                voices.push(Voice::new(notes));
+            } else if is_musicxml(&source) {
+                voices = read_musicxml(&source, &tuning, cli.tempo, sample_rate)?;
+            } else {
+                tokens.push(strip_comments(&source));
             }
-            None => None,
         }
     }
-}
-
-fn get_note(
-    note_name: &str,
-    tuning: f32,
-    sample_rate: u32,
-    duration: Duration,
-) -> Result<Note, String> {
-    Note::new(
-        get_frequency_from_note(note_name, tuning)?,
-        0.8,
-        sample_rate,
-        duration,
-    )
-}
+    if !midi && (!cli.track.is_empty() || !cli.channel.is_empty()) {
+        return Err("--track and --channel can only be used with a MIDI file".into());
+    }
 
-#[derive(Debug)]
-struct ArgumentParseError {
-    msg: String,
+    tokens.extend(get_sequence_tokens(cli)?);
+    voices.extend(parse_voices(&tokens, &tuning, cli.tempo, sample_rate)?);
+    Ok(Sequence::with_voices(voices, sample_rate))
 }
 
-impl ArgumentParseError {
-    fn new(msg: &str) -> Self {
-        ArgumentParseError {
-            msg: msg.to_string(),
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
         }
     }
 }
 
-impl fmt::Display for ArgumentParseError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.msg)
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    if cli.list_devices {
+        print!("{}", describe_devices());
+        return Ok(());
     }
-}
 
-impl Error for ArgumentParseError {
-    fn description(&self) -> &str {
-        &self.msg
-    }
-}
-
-fn parse_duration(s: &str, tempo: u32) -> Result<Duration, ArgumentParseError> {
-    let num = match s.parse::<u32>() {
-        Ok(n) => n,
-        Err(e) => {
-            return Err(ArgumentParseError::new(
-                format!("Failed parse integer from string '{s}': {e}").as_str(),
-            ))
+    if cli.output.is_some()
+        || cli.midi.is_some()
+        || cli.musicxml.is_some()
+        || cli.lilypond.is_some()
+    {
+        let sequence = get_sequence(&cli, cli.sample_rate)?;
+        if let Some(midi) = &cli.midi {
+            write_midi(midi, &sequence, cli.tempo, cli.midi_type)?;
         }
-    };
-
-    // Check if number is power of two
-    if (num != 0) && (num & (num - 1)) == 0 {
-        let beat_duration = 60.0 * 1000.0 / tempo as f32;
-        let beat = 1f32 / 4f32;
-        let scale = 1f32 / num as f32 / beat;
-        let duration = scale * beat_duration;
-        Ok(Duration::from_millis(duration as u64))
-    } else {
-        Err(ArgumentParseError::new(
-            format!("Note duration {num} was not a power of two").as_str(),
-        ))
+        if let Some(musicxml) = &cli.musicxml {
+            write_musicxml(musicxml, &sequence, cli.tempo)?;
+        }
+        if let Some(lilypond) = &cli.lilypond {
+            write_lilypond(lilypond, &sequence, cli.tempo)?;
+        }
+        if let Some(output) = &cli.output {
+            let mut player = Player::new(sequence);
+            player.set_waveform(cli.waveform);
+            player.set_envelope(get_envelope(&cli));
+            write_wav(output, &mut player, cli.channels, cli.bit_depth)?;
+        }
+        return Ok(());
     }
-}
 
-fn parse_notes(
-    s: &Vec<String>,
-    tuning: f32,
-    tempo: u32,
-    sample_rate: u32,
-) -> Result<Vec<Note>, String> {
-    let t: Result<Vec<Note>, ArgumentParseError> = s
-        .iter()
-        .map(|n| {
-            let notes: Vec<&str> = n.split(':').collect();
-            if notes.len() < 2 {
-                return Ok(get_note(notes[0], tuning, sample_rate, Duration::new(1, 0)).unwrap());
-            } else if notes.len() < 3 {
-                return Ok(get_note(
-                    notes[0],
-                    tuning,
-                    sample_rate,
-                    parse_duration(notes[1], tempo).unwrap(),
-                )
-                .unwrap());
-            } else {
-                return Err(ArgumentParseError::new(""));
-            }
-        })
-        .collect();
-    t.map_err(|e| e.to_string())
+    play(cli)
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let cli = Cli::parse();
-
-    let host = cpal::default_host();
-
-    let device = match cli.device {
-        Some(wanted_device) => host
-            .output_devices()
-            .expect("Failed to get output devices")
-            .find(|device| {
-                device.name().expect("Failed to access name of a device") == wanted_device
-            })
-            .expect(format!("Failed to find device {}", wanted_device).as_str()),
-        None => host.default_output_device().unwrap(),
+fn play(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let device = match &cli.device {
+        Some(wanted_device) => select_output_device(wanted_device)?,
+        None => cpal::default_host()
+            .default_output_device()
+            .ok_or("No default output device available")?,
     };
-    let config = get_device_config(&device, cli.sample_rate);
+    let config = get_device_config(&device, cli.sample_rate)?;
 
-    let mut notes = parse_notes(&cli.sequence, cli.tuning, cli.tempo, config.sample_rate.0)?;
+    let mut sequence = get_sequence(&cli, config.sample_rate().0)?;
     if cli.fermata {
-        let last = notes.len() - 1;
-        notes[last].num_samples = 0;
+        sequence.hold_last_note();
     }
 
-    let mut player = Player::new(notes, config.sample_rate.0);
+    let mut player = Player::new(sequence);
+    player.set_waveform(cli.waveform);
+    player.set_envelope(get_envelope(&cli));
 
     let done = Arc::new(AtomicBool::new(false));
-    let done_clone = Arc::clone(&done);
-
-    let stream = device
-        .build_output_stream(
-            &config,
-            move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                for output_sample in data.iter_mut() {
-                    match player.get_next_sample() {
-                        Some(sample) => {
-                            *output_sample = sample;
-                        }
-                        None => {
-                            done_clone.store(true, Ordering::SeqCst);
-                        }
-                    };
-                }
-            },
-            move |err| {
-                eprintln!("Output stream callback failed: {}", err);
-            },
-            None,
-        )
-        .expect("Failed to build output stream");
+    let stream = build_output_stream(&device, &config, player, Arc::clone(&done))?;
 
     stream.play().expect("Failed to play audio");
 
@@ -0,0 +1,702 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use midly::num::{u15, u24, u28, u4, u7};
+use midly::{
+    Format, Header, MetaMessage, MidiMessage, PitchBend, Smf, Timing, TrackEvent, TrackEventKind,
+};
+
+use crate::note::{Note, Pitch, A4_MIDI_KEY};
+use crate::sequence::{arrange_voices, Sequence, TimedNote, Timeline, Voice};
+use crate::tuning::Tuning;
+use crate::value::NoteValue;
+
+/// Resolution of exported MIDI files, chosen so that triplets and quintuplets of down to
+/// 64th notes fall on whole ticks.
+pub const TICKS_PER_QUARTER: u16 = 960;
+
+/// MIDI channel reserved for percussion, which voices are never assigned to.
+const PERCUSSION_CHANNEL: u8 = 9;
+
+/// Controller number of the pan position of a channel.
+const PAN_CONTROLLER: u8 = 10;
+
+/// Track layout of an exported Standard MIDI File.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MidiFormat {
+    /// Type 0, where every voice is merged into a single track.
+    SingleTrack,
+    /// Type 1, where the tempo is on the first track and every voice on a track of its own.
+    MultiTrack,
+}
+
+impl FromStr for MidiFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" => Ok(MidiFormat::SingleTrack),
+            "1" => Ok(MidiFormat::MultiTrack),
+            other => Err(format!("Invalid MIDI file type '{other}'. Must be 0 or 1.")),
+        }
+    }
+}
+
+impl fmt::Display for MidiFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MidiFormat::SingleTrack => write!(f, "0"),
+            MidiFormat::MultiTrack => write!(f, "1"),
+        }
+    }
+}
+
+/// Event at an absolute tick, ordered so that at the same tick controllers come first and
+/// notes are released before the next ones start.
+type TimedEvent = (u128, u8, TrackEventKind<'static>);
+
+/// Range of the pitch wheel in cents either way, which is two semitones unless a synthesizer
+/// is set up otherwise.
+const PITCH_BEND_RANGE: f32 = 200.0;
+
+/// MIDI note numbers of a note, taken from its pitches if it was written as note names, and
+/// the pitch bend that tunes them to quarter tones and cent offsets. Every pitch of a chord
+/// must deviate from its key by the same number of cents, since the bend applies to the
+/// whole channel.
+fn get_keys(note: &Note) -> Result<(Vec<u7>, PitchBend), String> {
+    let pitches: Vec<Pitch> = match note.pitches.is_empty() {
+        true => note
+            .frequencies
+            .iter()
+            .map(|&frequency| Pitch {
+                name: format!("{frequency} Hz"),
+                ..Pitch::from_frequency(frequency)
+            })
+            .collect(),
+        false => note.pitches.clone(),
+    };
+
+    let mut keys = Vec::new();
+    let mut bends = Vec::new();
+    for pitch in &pitches {
+        // The nearest key is bent by at most a quarter tone
+        let offset = (pitch.cents / 100.0).round();
+        let key = pitch.semitones + A4_MIDI_KEY + offset as i32;
+        let key = u8::try_from(key)
+            .ok()
+            .and_then(u7::try_from)
+            .ok_or_else(|| {
+                format!(
+                    "Pitch {} is outside the MIDI note range of 0-127",
+                    pitch.name
+                )
+            })?;
+        keys.push(key);
+        let cents = pitch.cents - 100.0 * offset;
+        bends.push(PitchBend::from_int(
+            (cents / PITCH_BEND_RANGE * 8192.0).round() as i16,
+        ));
+    }
+    match bends.split_first() {
+        None => Ok((keys, PitchBend::mid_raw_value())),
+        Some((&bend, rest)) if rest.iter().all(|&other| other == bend) => Ok((keys, bend)),
+        Some(_) => Err(format!(
+            "Chord of {} cannot be exported to MIDI, since its pitches deviate from the \
+            nearest keys by different numbers of cents",
+            pitches
+                .iter()
+                .map(|pitch| pitch.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Note value of a note, derived from its length in samples if it was not written with one.
+fn get_note_value(note: &Note, tempo: u32, sample_rate: u32) -> Result<NoteValue, String> {
+    match note.value {
+        Some(value) => Ok(value),
+        None => u64::try_from(note.num_samples * tempo as u128)
+            .map(|numerator| NoteValue::new(numerator, 240 * sample_rate as u64))
+            .map_err(|_| String::from("Note is too long to export to MIDI")),
+    }
+}
+
+fn get_velocity(amplitude: f32) -> u7 {
+    u7::new((amplitude * 127.0).round().clamp(1.0, 127.0) as u8)
+}
+
+/// Timed events of a voice played on `channel`, and the tick at which the voice ends.
+fn get_voice_events(
+    voice: &Voice,
+    channel: u4,
+    tempo: u32,
+    sample_rate: u32,
+) -> Result<(Vec<TimedEvent>, u128), String> {
+    let pan = ((voice.pan + 1.0) / 2.0 * 127.0).round().clamp(0.0, 127.0) as u8;
+    let mut events = vec![(
+        0,
+        0,
+        TrackEventKind::Midi {
+            channel,
+            message: MidiMessage::Controller {
+                controller: u7::new(PAN_CONTROLLER),
+                value: u7::new(pan),
+            },
+        },
+    )];
+
+    // Convert the absolute position of every note rather than each note value, so that
+    // rounding errors do not accumulate over the voice.
+    let mut position = NoteValue::zero();
+    let mut bend = PitchBend::mid_raw_value();
+    for note in &voice.notes {
+        let start = position.to_ticks(TICKS_PER_QUARTER);
+        position = position
+            .checked_add(get_note_value(note, tempo, sample_rate)?)
+            .ok_or("Sequence is too long to export to MIDI")?;
+        let end = position.to_ticks(TICKS_PER_QUARTER);
+        if start == end {
+            continue;
+        }
+
+        let vel = get_velocity(note.amplitude * voice.amplitude);
+        let (keys, note_bend) = get_keys(note)?;
+        // The bend changes after the previous note is released, which comes first at the tick
+        if !keys.is_empty() && note_bend != bend {
+            events.push((
+                start,
+                1,
+                TrackEventKind::Midi {
+                    channel,
+                    message: MidiMessage::PitchBend { bend: note_bend },
+                },
+            ));
+            bend = note_bend;
+        }
+        for key in keys {
+            events.push((
+                start,
+                2,
+                TrackEventKind::Midi {
+                    channel,
+                    message: MidiMessage::NoteOn { key, vel },
+                },
+            ));
+            events.push((
+                end,
+                1,
+                TrackEventKind::Midi {
+                    channel,
+                    message: MidiMessage::NoteOff { key, vel },
+                },
+            ));
+        }
+    }
+    Ok((events, position.to_ticks(TICKS_PER_QUARTER)))
+}
+
+/// Convert timed events into a track of delta-timed events that ends at `end`.
+fn get_track(mut events: Vec<TimedEvent>, end: u128) -> Result<Vec<TrackEvent<'static>>, String> {
+    events.sort_by_key(|&(tick, order, _)| (tick, order));
+    events.push((end, u8::MAX, TrackEventKind::Meta(MetaMessage::EndOfTrack)));
+
+    let mut previous = 0;
+    events
+        .into_iter()
+        .map(|(tick, _, kind)| {
+            let delta = u32::try_from(tick - previous)
+                .ok()
+                .and_then(u28::try_from)
+                .ok_or("Gap between MIDI events is too long")?;
+            previous = tick;
+            Ok(TrackEvent { delta, kind })
+        })
+        .collect()
+}
+
+/// Write `sequence` as a Standard MIDI File at `tempo`, with every voice on a channel of its
+/// own. Rests are kept as gaps between notes, including at the end of a voice. Quarter tones
+/// and cent offsets are played by bending the pitch of the channel.
+pub fn write_midi(
+    path: &Path,
+    sequence: &Sequence,
+    tempo: u32,
+    format: MidiFormat,
+) -> Result<(), String> {
+    let channels: Vec<u4> = (0..16)
+        .filter(|&channel| channel != PERCUSSION_CHANNEL)
+        .map(u4::new)
+        .collect();
+    if sequence.voices().len() > channels.len() {
+        return Err(format!(
+            "Cannot export {} voices to MIDI, which has {} melodic channels",
+            sequence.voices().len(),
+            channels.len()
+        ));
+    }
+
+    let micros_per_quarter = u24::try_from(60_000_000 / tempo)
+        .ok_or_else(|| format!("Tempo {tempo} is too slow to export to MIDI"))?;
+    let tempo_event = (
+        0,
+        0,
+        TrackEventKind::Meta(MetaMessage::Tempo(micros_per_quarter)),
+    );
+    let mut voices = Vec::new();
+    for (voice, &channel) in sequence.voices().iter().zip(&channels) {
+        voices.push(get_voice_events(
+            voice,
+            channel,
+            tempo,
+            sequence.sample_rate(),
+        )?);
+    }
+
+    let tracks = match format {
+        MidiFormat::SingleTrack => {
+            let end = voices.iter().map(|&(_, end)| end).max().unwrap_or(0);
+            let mut events = vec![tempo_event];
+            events.extend(voices.into_iter().flat_map(|(events, _)| events));
+            vec![get_track(events, end)?]
+        }
+        MidiFormat::MultiTrack => {
+            let mut tracks = vec![get_track(vec![tempo_event], 0)?];
+            for (events, end) in voices {
+                tracks.push(get_track(events, end)?);
+            }
+            tracks
+        }
+    };
+
+    let header = Header::new(
+        match format {
+            MidiFormat::SingleTrack => Format::SingleTrack,
+            MidiFormat::MultiTrack => Format::Parallel,
+        },
+        Timing::Metrical(u15::new(TICKS_PER_QUARTER)),
+    );
+    Smf { header, tracks }
+        .save(path)
+        .map_err(|e| format!("Failed to write MIDI file '{}': {e}", path.display()))
+}
+
+/// Tempo of a MIDI file until its first tempo change, which is 120 beats per minute.
+const DEFAULT_MICROS_PER_QUARTER: u32 = 500_000;
+
+/// Whether `data` is a Standard MIDI File, possibly wrapped in a RIFF container.
+pub fn is_midi(data: &[u8]) -> bool {
+    data.starts_with(b"MThd") || (data.starts_with(b"RIFF") && data.get(8..12) == Some(b"RMID"))
+}
+
+/// Notes played on one channel of one track.
+#[derive(Default)]
+struct Part {
+    notes: Vec<TimedNote<u64>>,
+    /// First pan position set on the channel.
+    pan: Option<u8>,
+    /// Tick at which the track ends, which can be after the last note.
+    end: u64,
+}
+
+/// Tempo changes of a MIDI file, used to convert ticks into samples.
+struct TempoMap {
+    timing: Timing,
+    /// Tick and microseconds per quarter note of every tempo change, in order.
+    changes: Vec<(u64, u32)>,
+}
+
+impl TempoMap {
+    fn new(smf: &Smf) -> Self {
+        let mut changes = Vec::new();
+        for track in &smf.tracks {
+            let mut tick = 0;
+            for event in track {
+                tick += event.delta.as_int() as u64;
+                if let TrackEventKind::Meta(MetaMessage::Tempo(micros)) = event.kind {
+                    changes.push((tick, micros.as_int()));
+                }
+            }
+        }
+        // Stable, so that the last of several changes at the same tick wins
+        changes.sort_by_key(|&(tick, _)| tick);
+        TempoMap {
+            timing: smf.header.timing,
+            changes,
+        }
+    }
+}
+
+impl Timeline<u64> for TempoMap {
+    fn to_samples(&self, tick: u64, sample_rate: u32) -> u128 {
+        match self.timing {
+            Timing::Metrical(ticks_per_quarter) => {
+                // Sum ticks times microseconds per quarter note over every tempo segment, and
+                // divide only once at the end.
+                let (mut micro_ticks, mut previous, mut micros) =
+                    (0u128, 0, DEFAULT_MICROS_PER_QUARTER);
+                for &(change, change_micros) in self.changes.iter().take_while(|&&(t, _)| t < tick)
+                {
+                    micro_ticks += (change - previous) as u128 * micros as u128;
+                    (previous, micros) = (change, change_micros);
+                }
+                micro_ticks += (tick - previous) as u128 * micros as u128;
+                let num = micro_ticks * sample_rate as u128;
+                let den = ticks_per_quarter.as_int().max(1) as u128 * 1_000_000;
+                (2 * num + den) / (2 * den)
+            }
+            Timing::Timecode(fps, subframes) => {
+                let ticks_per_second = fps.as_f32() as f64 * subframes.max(1) as f64;
+                (tick as f64 * sample_rate as f64 / ticks_per_second).round() as u128
+            }
+        }
+    }
+
+    fn to_note_value(&self, start: u64, end: u64) -> Option<NoteValue> {
+        match self.timing {
+            Timing::Metrical(ticks_per_quarter) => Some(NoteValue::new(
+                end - start,
+                4 * ticks_per_quarter.as_int().max(1) as u64,
+            )),
+            Timing::Timecode(..) => None,
+        }
+    }
+}
+
+fn get_timed_note(start: u64, end: u64, key: u8, velocity: u8) -> TimedNote<u64> {
+    TimedNote {
+        start,
+        end,
+        pitch: Pitch::from_midi_key(key),
+        amplitude: velocity as f32 / 127.0,
+    }
+}
+
+/// Collect the notes of `track` into a part per channel, keeping only `channels` (numbered
+/// from 1) unless it is empty.
+fn get_track_parts(track: &[TrackEvent], channels: &[u8]) -> BTreeMap<u8, Part> {
+    let mut parts: BTreeMap<u8, Part> = BTreeMap::new();
+    // Start tick and velocity of sounding notes by channel and key
+    let mut sounding: BTreeMap<(u8, u8), (u64, u8)> = BTreeMap::new();
+    let mut tick = 0;
+    for event in track {
+        tick += event.delta.as_int() as u64;
+        let TrackEventKind::Midi { channel, message } = event.kind else {
+            continue;
+        };
+        let channel = channel.as_int();
+        if !channels.is_empty() && !channels.contains(&(channel + 1)) {
+            continue;
+        }
+        let part = parts.entry(channel).or_default();
+        let (key, velocity) = match message {
+            MidiMessage::NoteOn { key, vel } => (key.as_int(), vel.as_int()),
+            MidiMessage::NoteOff { key, .. } => (key.as_int(), 0),
+            MidiMessage::Controller { controller, value }
+                if controller.as_int() == PAN_CONTROLLER =>
+            {
+                part.pan.get_or_insert(value.as_int());
+                continue;
+            }
+            _ => continue,
+        };
+
+        // A note on of a sounding key retriggers it, and a velocity of zero is a note off
+        if let Some((start, velocity)) = sounding.remove(&(channel, key)) {
+            if start < tick {
+                part.notes.push(get_timed_note(start, tick, key, velocity));
+            }
+        }
+        if velocity > 0 {
+            sounding.insert((channel, key), (tick, velocity));
+        }
+    }
+
+    // Notes still sounding end with the track
+    for ((channel, key), (start, velocity)) in sounding {
+        if start < tick {
+            let note = get_timed_note(start, tick, key, velocity);
+            parts.entry(channel).or_default().notes.push(note);
+        }
+    }
+    parts.retain(|_, part| !part.notes.is_empty());
+    for part in parts.values_mut() {
+        part.end = tick;
+    }
+    parts
+}
+
+/// Read the notes of a Standard MIDI File into voices, keeping only the `tracks` (numbered
+/// from 0) and `channels` (numbered from 1) given, or every track and channel if they are
+/// empty. Each channel of a track becomes one or more voices, as many as it plays
+/// overlapping notes, and every tempo change of the file is followed.
+pub fn read_midi(
+    data: &[u8],
+    tracks: &[usize],
+    channels: &[u8],
+    tuning: &Tuning,
+    sample_rate: u32,
+) -> Result<Vec<Voice>, String> {
+    let smf = Smf::parse(data).map_err(|e| format!("Failed to parse MIDI file: {e}"))?;
+    if smf.header.format == Format::Sequential {
+        return Err(String::from(
+            "MIDI files of type 2, with independent sequential tracks, are not supported",
+        ));
+    }
+    if let Some(track) = tracks.iter().find(|&&track| track >= smf.tracks.len()) {
+        return Err(format!(
+            "No track {track} in MIDI file, which has {} tracks numbered from 0",
+            smf.tracks.len()
+        ));
+    }
+
+    let tempo_map = TempoMap::new(&smf);
+    let mut voices = Vec::new();
+    for (index, track) in smf.tracks.iter().enumerate() {
+        if !tracks.is_empty() && !tracks.contains(&index) {
+            continue;
+        }
+        for part in get_track_parts(track, channels).into_values() {
+            let pan = part
+                .pan
+                .map_or(0.0, |pan| ((pan as f32 - 64.0) / 63.0).clamp(-1.0, 1.0));
+            voices.extend(arrange_voices(
+                part.notes,
+                part.end,
+                pan,
+                &tempo_map,
+                tuning,
+                sample_rate,
+            )?);
+        }
+    }
+    if voices.is_empty() {
+        return Err(String::from(
+            "No notes found in the selected tracks and channels of the MIDI file",
+        ));
+    }
+    Ok(voices)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::parser::parse_voices;
+
+    const TEMPO: u32 = 120;
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn parse(source: &str) -> Sequence {
+        let voices = parse_voices(
+            &[source.to_string()],
+            &Tuning::new(440.0),
+            TEMPO,
+            SAMPLE_RATE,
+        )
+        .unwrap();
+        Sequence::with_voices(voices, SAMPLE_RATE)
+    }
+
+    /// Export `sequence` with `format` to a temporary file called `name` and read its bytes.
+    fn export(name: &str, sequence: &Sequence, format: MidiFormat) -> Vec<u8> {
+        let path = std::env::temp_dir().join(format!(
+            "noteseq-{}-{name}-{format}.mid",
+            std::process::id()
+        ));
+        write_midi(&path, sequence, TEMPO, format).unwrap();
+        let data = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        data
+    }
+
+    /// Absolute tick, channel, key and whether it is a note on of every note event of `track`.
+    fn note_events(track: &[TrackEvent]) -> Vec<(u32, u8, u8, bool)> {
+        let mut tick = 0;
+        let mut events = Vec::new();
+        for event in track {
+            tick += event.delta.as_int();
+            if let TrackEventKind::Midi { channel, message } = event.kind {
+                match message {
+                    MidiMessage::NoteOn { key, .. } => {
+                        events.push((tick, channel.as_int(), key.as_int(), true))
+                    }
+                    MidiMessage::NoteOff { key, .. } => {
+                        events.push((tick, channel.as_int(), key.as_int(), false))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn export_single_track() {
+        let data = export(
+            "single",
+            &parse("C4:4 r:8 [E4 G4]:8 | A4:2"),
+            MidiFormat::SingleTrack,
+        );
+        let smf = Smf::parse(&data).unwrap();
+        assert_eq!(smf.header.format, Format::SingleTrack);
+        assert_eq!(
+            smf.header.timing,
+            Timing::Metrical(u15::new(TICKS_PER_QUARTER))
+        );
+        assert_eq!(smf.tracks.len(), 1);
+        assert!(
+            smf.tracks[0]
+                .iter()
+                .any(|event| event.kind
+                    == TrackEventKind::Meta(MetaMessage::Tempo(u24::new(500_000))))
+        );
+        assert_eq!(
+            note_events(&smf.tracks[0]),
+            [
+                (0, 0, 60, true),
+                (0, 1, 69, true),
+                (960, 0, 60, false),
+                (1440, 0, 64, true),
+                (1440, 0, 67, true),
+                (1920, 0, 64, false),
+                (1920, 0, 67, false),
+                (1920, 1, 69, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn export_multi_track() {
+        let voices: Vec<String> = (0..16).map(|_| String::from("C4:4")).collect();
+        let sequence = parse(&voices[..15].join(" | "));
+        let data = export("multi", &sequence, MidiFormat::MultiTrack);
+        let smf = Smf::parse(&data).unwrap();
+        assert_eq!(smf.header.format, Format::Parallel);
+        assert_eq!(smf.tracks.len(), 16);
+        let channels: Vec<u8> = smf.tracks[1..]
+            .iter()
+            .map(|track| note_events(track)[0].1)
+            .collect();
+        assert!(!channels.contains(&PERCUSSION_CHANNEL));
+
+        let path = std::env::temp_dir().join("noteseq-too-many-voices.mid");
+        let too_many = parse(&voices.join(" | "));
+        assert!(write_midi(&path, &too_many, TEMPO, MidiFormat::MultiTrack).is_err());
+    }
+
+    #[test]
+    fn export_out_of_range() {
+        let path = std::env::temp_dir().join("noteseq-out-of-range.mid");
+        let error = write_midi(&path, &parse("C10:4"), TEMPO, MidiFormat::SingleTrack);
+        assert_eq!(
+            error,
+            Err(String::from(
+                "Pitch C10 is outside the MIDI note range of 0-127"
+            ))
+        );
+    }
+
+    /// Export `source` with `format` to a temporary file called `name` and import it again.
+    fn round_trip(name: &str, source: &str, format: MidiFormat) -> (Vec<Voice>, Vec<Voice>) {
+        let sequence = parse(source);
+        let data = export(name, &sequence, format);
+        let read = read_midi(&data, &[], &[], &Tuning::new(440.0), SAMPLE_RATE).unwrap();
+        (sequence.voices().to_vec(), read)
+    }
+
+    fn summary(voice: &Voice) -> Vec<(Vec<i32>, u128, Option<NoteValue>)> {
+        voice
+            .notes
+            .iter()
+            .map(|note| {
+                let semitones = note.pitches.iter().map(|pitch| pitch.semitones).collect();
+                (semitones, note.num_samples, note.value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trip_notes() {
+        for format in [MidiFormat::SingleTrack, MidiFormat::MultiTrack] {
+            let (written, read) = round_trip(
+                "notes",
+                "C4:4 r:8 E4:8. [G4 C5]:2 {3:2 A4:8 B4:8 C5:8} C#5:2~C#5:16 r:16",
+                format,
+            );
+            assert_eq!(read.len(), 1);
+            assert_eq!(summary(&read[0]), summary(&written[0]));
+        }
+    }
+
+    #[test]
+    fn round_trip_voices() {
+        let (written, read) = round_trip(
+            "voices",
+            "@pan:-1 C4:2 D4:2 | @pan:1 r:4 m36:4 m127:2",
+            MidiFormat::MultiTrack,
+        );
+        assert_eq!(read.len(), 2);
+        for (read, written) in read.iter().zip(&written) {
+            assert_eq!(summary(read), summary(written));
+            assert!((read.pan - written.pan).abs() < 0.02);
+        }
+    }
+
+    #[test]
+    fn import_selection() {
+        let data = export(
+            "selection",
+            &parse("C4:2 | D4:4 E4:4"),
+            MidiFormat::MultiTrack,
+        );
+        let tuning = Tuning::new(440.0);
+        let read = |tracks: &[usize], channels: &[u8]| {
+            read_midi(&data, tracks, channels, &tuning, SAMPLE_RATE).unwrap()
+        };
+        assert_eq!(read(&[], &[]).len(), 2);
+        assert_eq!(read(&[2], &[])[0].notes[0].pitches[0].semitones, -7);
+        assert_eq!(read(&[], &[1])[0].notes[0].pitches[0].semitones, -9);
+        assert!(read_midi(&data, &[1], &[2], &tuning, SAMPLE_RATE).is_err());
+        assert!(read_midi(b"MThd", &[], &[], &tuning, SAMPLE_RATE).is_err());
+    }
+
+    #[test]
+    fn export_pitch_bends() {
+        let data = export(
+            "bends",
+            &parse("A+4:4 A4:4 [C4+10c E4+10c]:4 r:4 445hz:4"),
+            MidiFormat::SingleTrack,
+        );
+        let smf = Smf::parse(&data).unwrap();
+        let keys: Vec<u8> = note_events(&smf.tracks[0])
+            .into_iter()
+            .filter(|&(_, _, _, on)| on)
+            .map(|(_, _, key, _)| key)
+            .collect();
+        assert_eq!(keys, [70, 69, 60, 64, 69]);
+
+        let mut tick = 0;
+        let mut bends = Vec::new();
+        for event in &smf.tracks[0] {
+            tick += event.delta.as_int();
+            if let TrackEventKind::Midi {
+                message: MidiMessage::PitchBend { bend },
+                ..
+            } = event.kind
+            {
+                bends.push((tick, bend.as_int()));
+            }
+        }
+        assert_eq!(bends, [(0, -2048), (960, 0), (1920, 410), (3840, 801)]);
+
+        let path = std::env::temp_dir().join("noteseq-mixed-bends.mid");
+        let error = write_midi(&path, &parse("[C4 E+4]:4"), TEMPO, MidiFormat::SingleTrack);
+        assert!(error
+            .unwrap_err()
+            .starts_with("Chord of C4, E+4 cannot be exported"));
+    }
+}
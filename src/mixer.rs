@@ -0,0 +1,52 @@
+/// Level above which the mixer starts to compress the signal.
+const SOFT_CLIP_THRESHOLD: f32 = 0.8;
+
+/// Saturate samples above [`SOFT_CLIP_THRESHOLD`] smoothly towards 1 instead of hard clipping.
+fn soft_clip(sample: f32) -> f32 {
+    let magnitude = sample.abs();
+    if magnitude <= SOFT_CLIP_THRESHOLD {
+        return sample;
+    }
+    let headroom = 1.0 - SOFT_CLIP_THRESHOLD;
+    let compressed =
+        SOFT_CLIP_THRESHOLD + headroom * ((magnitude - SOFT_CLIP_THRESHOLD) / headroom).tanh();
+    compressed.copysign(sample)
+}
+
+/// Sums simultaneous pitches into one sample within [-1, 1].
+pub struct Mixer {
+    gain: f32,
+}
+
+impl Mixer {
+    /// Create a mixer for at most `polyphony` simultaneous pitches.
+    ///
+    /// The sum is scaled by the inverse square root of the polyphony, which keeps the loudness
+    /// of chords comparable to single notes. The gain is fixed for the whole sequence, so that
+    /// one voice changing its number of pitches does not change the level of the others.
+    pub fn new(polyphony: usize) -> Self {
+        Mixer {
+            gain: 1.0 / (polyphony.max(1) as f32).sqrt(),
+        }
+    }
+
+    /// Mix the samples into one, soft clipping peaks that still exceed the threshold.
+    pub fn mix<I: IntoIterator<Item = f32>>(&self, samples: I) -> f32 {
+        soft_clip(samples.into_iter().sum::<f32>() * self.gain)
+    }
+
+    /// Mix stereo frames of left and right samples into one frame, see [`Mixer::mix`].
+    pub fn mix_stereo<I: IntoIterator<Item = (f32, f32)>>(&self, frames: I) -> (f32, f32) {
+        let (left, right) = frames
+            .into_iter()
+            .fold((0.0, 0.0), |(left, right), (l, r)| (left + l, right + r));
+        (self.mix([left]), self.mix([right]))
+    }
+}
+
+/// Spread a mono sample over a left and right channel with a constant power pan law, where
+/// `pan` goes from -1 (left) to 1 (right).
+pub fn pan(sample: f32, pan: f32) -> (f32, f32) {
+    let angle = (pan.clamp(-1.0, 1.0) + 1.0) * std::f32::consts::FRAC_PI_4;
+    (sample * angle.cos(), sample * angle.sin())
+}
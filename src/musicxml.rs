@@ -0,0 +1,733 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use roxmltree::{Document, Node, ParsingOptions};
+
+use crate::notation::{is_bass, notate_voice, NotatedNote};
+use crate::note::{Pitch, DEFAULT_AMPLITUDE};
+use crate::sequence::{arrange_voices, Sequence, TimedNote, Timeline, Voice};
+use crate::tuning::Tuning;
+use crate::value::{gcd, NoteValue};
+
+/// Largest alteration of a pitch in semitones, beyond triple sharps and flats.
+const MAX_ALTER: f32 = 4.0;
+
+/// Names of note types by the base-2 logarithm of their divisor of a whole note.
+const NOTE_TYPES: [&str; 11] = [
+    "whole", "half", "quarter", "eighth", "16th", "32nd", "64th", "128th", "256th", "512th",
+    "1024th",
+];
+
+/// Whether `source` looks like an uncompressed MusicXML score.
+pub fn is_musicxml(source: &str) -> bool {
+    source.trim_start().starts_with('<')
+        && (source.contains("<score-partwise") || source.contains("<score-timewise"))
+}
+
+/// Tempo changes of a score, used to convert positions into samples.
+struct TempoMap {
+    /// Position as a fraction of a whole note and tempo in quarter notes per minute of every
+    /// tempo change, starting with the tempo at the start of the score.
+    changes: Vec<(NoteValue, f64)>,
+}
+
+impl Timeline<NoteValue> for TempoMap {
+    fn to_samples(&self, position: NoteValue, sample_rate: u32) -> u128 {
+        let mut seconds = 0.0;
+        for (i, &(change, tempo)) in self.changes.iter().enumerate() {
+            if change >= position {
+                break;
+            }
+            let next = self
+                .changes
+                .get(i + 1)
+                .map_or(position, |&(next, _)| next.min(position));
+            let length = next.checked_sub(change).unwrap_or_default();
+            seconds += length.numerator() as f64 / length.denominator() as f64 * 240.0 / tempo;
+        }
+        (seconds * sample_rate as f64).round() as u128
+    }
+
+    fn to_note_value(&self, start: NoteValue, end: NoteValue) -> Option<NoteValue> {
+        end.checked_sub(start)
+    }
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(node, name)?.text().map(str::trim)
+}
+
+/// Pan positions of the parts of the score by part id, from their MIDI instruments.
+fn get_part_pans<'a>(score: Node<'a, '_>) -> HashMap<&'a str, f32> {
+    let mut pans = HashMap::new();
+    let parts = child(score, "part-list")
+        .into_iter()
+        .flat_map(|list| list.children())
+        .filter(|node| node.has_tag_name("score-part"));
+    for part in parts {
+        let pan = part
+            .descendants()
+            .find(|node| node.has_tag_name("pan"))
+            .and_then(|pan| pan.text()?.trim().parse::<f32>().ok());
+        if let (Some(id), Some(pan)) = (part.attribute("id"), pan) {
+            // MusicXML gives the pan as an angle from -90 (left) to 90 (right) degrees
+            pans.insert(id, (pan / 90.0).clamp(-1.0, 1.0));
+        }
+    }
+    pans
+}
+
+/// Reader of the notes of a part, which keeps track of the position in the part as a
+/// fraction of a whole note.
+struct PartReader<'a> {
+    id: &'a str,
+    measure: &'a str,
+    /// Divisions of a quarter note that durations are given in.
+    divisions: u64,
+    position: NoteValue,
+    /// Start of the last note, which following notes of the same chord start at.
+    last_start: NoteValue,
+    notes: Vec<TimedNote<NoteValue>>,
+    /// Index of the note tied to the next note of the same pitch, by pitch in semitones.
+    ties: HashMap<i32, usize>,
+    tempo_changes: Vec<(NoteValue, f64)>,
+}
+
+impl<'a> PartReader<'a> {
+    fn error(&self, msg: &str) -> String {
+        format!("Measure {} of part {}: {msg}", self.measure, self.id)
+    }
+
+    fn get_duration(&self, node: Node) -> Result<NoteValue, String> {
+        let duration = child_text(node, "duration").ok_or_else(|| {
+            self.error(&format!(
+                "<{}> is missing its duration",
+                node.tag_name().name()
+            ))
+        })?;
+        let duration = duration
+            .parse::<u64>()
+            .map_err(|_| self.error(&format!("Invalid duration '{duration}'")))?;
+        self.divisions
+            .checked_mul(4)
+            .map(|divisions| NoteValue::new(duration, divisions))
+            .ok_or_else(|| self.error("Divisions are too fine"))
+    }
+
+    fn advance(&mut self, value: NoteValue) -> Result<NoteValue, String> {
+        self.position
+            .checked_add(value)
+            .ok_or_else(|| self.error("Score is too long"))
+    }
+
+    fn get_pitch(&self, pitch: Node) -> Result<Pitch, String> {
+        let step = child_text(pitch, "step").unwrap_or_default();
+        let octave = child_text(pitch, "octave").unwrap_or_default();
+        // Microtonal alterations are spelled with the nearest semitone and the cents that remain,
+        // where quarter tones are spelled from the semitone closer to the natural, e.g. 1.5 is a
+        // sharp raised by 50 cents
+        let alter = match child_text(pitch, "alter") {
+            Some(alter) => alter
+                .parse::<f32>()
+                .ok()
+                .filter(|alter| alter.abs() <= MAX_ALTER)
+                .ok_or_else(|| {
+                    self.error(&format!(
+                        "Invalid alteration '{alter}', must be at most {MAX_ALTER} semitones"
+                    ))
+                })?,
+            None => 0.0,
+        };
+        let semitones = alter.signum() * (alter.abs() - 0.5).ceil();
+        let accidental = match semitones < 0.0 {
+            true => "b",
+            false => "#",
+        };
+        let name = format!(
+            "{step}{}{octave}",
+            accidental.repeat(semitones.abs() as usize)
+        );
+        let pitch = name.parse::<Pitch>().map_err(|e| self.error(&e))?;
+        Ok(Pitch {
+            cents: 100.0 * (alter - semitones),
+            ..pitch
+        })
+    }
+
+    fn read_note(&mut self, note: Node) -> Result<(), String> {
+        // Grace notes take no time, and cue notes are not played
+        if child(note, "grace").is_some() || child(note, "cue").is_some() {
+            return Ok(());
+        }
+        let value = self.get_duration(note)?;
+        let start = match child(note, "chord") {
+            Some(_) => self.last_start,
+            None => {
+                let start = self.position;
+                self.position = self.advance(value)?;
+                start
+            }
+        };
+        self.last_start = start;
+        let end = start
+            .checked_add(value)
+            .ok_or_else(|| self.error("Score is too long"))?;
+
+        // Rests and unpitched percussion notes are silent
+        let Some(pitch) = child(note, "pitch") else {
+            return Ok(());
+        };
+        let pitch = self.get_pitch(pitch)?;
+        let semitones = pitch.semitones;
+        let tie = |kind| {
+            note.children()
+                .any(|child| child.has_tag_name("tie") && child.attribute("type") == Some(kind))
+        };
+
+        let tied = self
+            .ties
+            .remove(&semitones)
+            .filter(|&index| tie("stop") && self.notes[index].end == start);
+        let index = match tied {
+            Some(index) => {
+                self.notes[index].end = end;
+                index
+            }
+            None => {
+                self.notes.push(TimedNote {
+                    start,
+                    end,
+                    pitch,
+                    amplitude: DEFAULT_AMPLITUDE,
+                });
+                self.notes.len() - 1
+            }
+        };
+        if tie("start") {
+            self.ties.insert(semitones, index);
+        }
+        Ok(())
+    }
+
+    fn read_tempo(&mut self, sound: Node) -> Result<(), String> {
+        if let Some(tempo) = sound.attribute("tempo") {
+            match tempo.trim().parse::<f64>() {
+                Ok(tempo) if tempo > 0.0 => self.tempo_changes.push((self.position, tempo)),
+                _ => return Err(self.error(&format!("Invalid tempo '{tempo}'"))),
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the measures of `part`, and return the position at which the part ends.
+    fn read_part(&mut self, part: Node<'a, '_>) -> Result<NoteValue, String> {
+        let mut measure_start = NoteValue::zero();
+        for measure in part.children().filter(|node| node.has_tag_name("measure")) {
+            self.measure = measure.attribute("number").unwrap_or("?");
+            self.position = measure_start;
+            let mut measure_end = measure_start;
+            for element in measure.children().filter(Node::is_element) {
+                match element.tag_name().name() {
+                    "attributes" => {
+                        if let Some(divisions) = child_text(element, "divisions") {
+                            self.divisions = match divisions.parse::<u64>() {
+                                Ok(divisions) if divisions > 0 => divisions,
+                                _ => {
+                                    return Err(
+                                        self.error(&format!("Invalid divisions '{divisions}'"))
+                                    )
+                                }
+                            };
+                        }
+                    }
+                    "note" => self.read_note(element)?,
+                    "backup" => {
+                        self.position = self
+                            .position
+                            .checked_sub(self.get_duration(element)?)
+                            .ok_or_else(|| self.error("<backup> goes before the start"))?;
+                    }
+                    "forward" => {
+                        let value = self.get_duration(element)?;
+                        self.position = self.advance(value)?;
+                    }
+                    "direction" => {
+                        for sound in element.descendants().filter(|n| n.has_tag_name("sound")) {
+                            self.read_tempo(sound)?;
+                        }
+                    }
+                    "sound" => self.read_tempo(element)?,
+                    _ => {}
+                }
+                measure_end = measure_end.max(self.position);
+            }
+            measure_start = measure_end;
+        }
+        Ok(measure_start)
+    }
+}
+
+/// Read a MusicXML score in `score-partwise` format into voices. Each part becomes one or
+/// more voices, as many as it plays overlapping notes, and pitches are spelled as note names
+/// and tuned like the notes of a sequence. Durations are given in divisions of a quarter
+/// note, so tuplets play for the time they are written to take. Tied notes are joined into
+/// one, and the score is played at `tempo` until it sets a tempo of its own.
+pub fn read_musicxml(
+    source: &str,
+    tuning: &Tuning,
+    tempo: u32,
+    sample_rate: u32,
+) -> Result<Vec<Voice>, String> {
+    let options = ParsingOptions {
+        allow_dtd: true,
+        ..ParsingOptions::default()
+    };
+    let document = Document::parse_with_options(source, options)
+        .map_err(|e| format!("Failed to parse MusicXML: {e}"))?;
+    let score = document.root_element();
+    match score.tag_name().name() {
+        "score-partwise" => {}
+        "score-timewise" => {
+            return Err(String::from(
+                "MusicXML scores in score-timewise format are not supported, \
+                convert them to score-partwise",
+            ))
+        }
+        other => return Err(format!("Expected a MusicXML score, found <{other}>")),
+    }
+
+    let pans = get_part_pans(score);
+    let mut parts = Vec::new();
+    let mut tempo_changes = vec![(NoteValue::zero(), tempo as f64)];
+    for part in score.children().filter(|node| node.has_tag_name("part")) {
+        let id = part.attribute("id").unwrap_or("?");
+        let mut reader = PartReader {
+            id,
+            measure: "?",
+            divisions: 1,
+            position: NoteValue::zero(),
+            last_start: NoteValue::zero(),
+            notes: Vec::new(),
+            ties: HashMap::new(),
+            tempo_changes: Vec::new(),
+        };
+        let end = reader.read_part(part)?;
+        tempo_changes.extend(reader.tempo_changes);
+        parts.push((reader.notes, end, pans.get(id).copied().unwrap_or(0.0)));
+    }
+
+    // Stable, so that the tempo set last at a position wins
+    tempo_changes.sort_by_key(|&(position, _)| position);
+    let tempo_map = TempoMap {
+        changes: tempo_changes,
+    };
+    let mut voices = Vec::new();
+    for (notes, end, pan) in parts {
+        voices.extend(arrange_voices(
+            notes,
+            end,
+            pan,
+            &tempo_map,
+            tuning,
+            sample_rate,
+        )?);
+    }
+    if voices.is_empty() {
+        return Err(String::from("No notes found in the MusicXML score"));
+    }
+    Ok(voices)
+}
+
+/// Write a notated note, or one element per pitch of a chord.
+fn write_note(
+    xml: &mut String,
+    note: &NotatedNote,
+    tied_from_previous: bool,
+    divisions: u64,
+) -> Result<(), String> {
+    let duration = note
+        .value
+        .value
+        .checked_mul(NoteValue::new(4 * divisions, 1))
+        .filter(|duration| duration.denominator() == 1)
+        .ok_or_else(|| String::from("Note is too short to export to MusicXML"))?
+        .numerator();
+    let pitches: Vec<Option<&Pitch>> = match note.pitches.is_empty() {
+        true => vec![None],
+        false => note.pitches.iter().map(Some).collect(),
+    };
+    for (i, pitch) in pitches.into_iter().enumerate() {
+        xml.push_str("      <note>\n");
+        if i > 0 {
+            xml.push_str("        <chord/>\n");
+        }
+        match pitch {
+            Some(pitch) => {
+                let (step, alter, octave) = pitch.spelling();
+                if !(0..=9).contains(&octave) {
+                    return Err(format!(
+                        "Cannot export {} to MusicXML, which only has octaves 0-9",
+                        pitch.name
+                    ));
+                }
+                let alter = alter as f32 + pitch.cents / 100.0;
+                let _ = write!(xml, "        <pitch><step>{step}</step>");
+                if alter != 0.0 {
+                    let _ = write!(xml, "<alter>{alter}</alter>");
+                }
+                let _ = writeln!(xml, "<octave>{octave}</octave></pitch>");
+            }
+            None => xml.push_str("        <rest/>\n"),
+        }
+        let _ = writeln!(xml, "        <duration>{duration}</duration>");
+        if tied_from_previous {
+            xml.push_str("        <tie type=\"stop\"/>\n");
+        }
+        if note.tie {
+            xml.push_str("        <tie type=\"start\"/>\n");
+        }
+        xml.push_str("        <voice>1</voice>\n");
+        let _ = writeln!(
+            xml,
+            "        <type>{}</type>",
+            NOTE_TYPES[note.value.divisor_log as usize]
+        );
+        for _ in 0..note.value.dots {
+            xml.push_str("        <dot/>\n");
+        }
+        if let Some((actual, normal)) = note.value.tuplet {
+            let _ = writeln!(
+                xml,
+                "        <time-modification><actual-notes>{actual}</actual-notes>\
+                <normal-notes>{normal}</normal-notes></time-modification>"
+            );
+        }
+        if tied_from_previous || note.tie {
+            xml.push_str("        <notations>");
+            if tied_from_previous {
+                xml.push_str("<tied type=\"stop\"/>");
+            }
+            if note.tie {
+                xml.push_str("<tied type=\"start\"/>");
+            }
+            xml.push_str("</notations>\n");
+        }
+        xml.push_str("      </note>\n");
+    }
+    Ok(())
+}
+
+/// Write `sequence` to a MusicXML score in `score-partwise` format, with one part per voice
+/// in 4/4 at `tempo`. Pitches are spelled as they were written, and note values are the ones
+/// they were written with, or inferred from their lengths for notes created from lengths in
+/// samples. Notes crossing bar lines are split into tied notes.
+pub fn write_musicxml(path: &Path, sequence: &Sequence, tempo: u32) -> Result<(), String> {
+    let mut parts = Vec::new();
+    for voice in sequence.voices() {
+        parts.push(notate_voice(voice, tempo, sequence.sample_rate())?);
+    }
+    // Divisions of a quarter note that every duration is a whole number of
+    let divisions = parts
+        .iter()
+        .flatten()
+        .flatten()
+        .map(|note| {
+            let denominator = note.value.value.denominator() as u128;
+            denominator / gcd(denominator, 4)
+        })
+        .try_fold(1u128, |divisions, d| {
+            (divisions / gcd(divisions, d)).checked_mul(d)
+        })
+        .and_then(|divisions| u64::try_from(divisions).ok())
+        .ok_or_else(|| String::from("Note values are too fine to export to MusicXML"))?;
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n\
+        <!DOCTYPE score-partwise PUBLIC \"-//Recordare//DTD MusicXML 4.0 Partwise//EN\" \
+        \"http://www.musicxml.org/dtds/partwise.dtd\">\n\
+        <score-partwise version=\"4.0\">\n  <part-list>\n",
+    );
+    for (i, voice) in sequence.voices().iter().enumerate() {
+        let _ = writeln!(
+            xml,
+            "    <score-part id=\"P{n}\">\n      <part-name>Voice {n}</part-name>\n      \
+            <score-instrument id=\"P{n}-I1\"><instrument-name>Synthesizer</instrument-name>\
+            </score-instrument>\n      \
+            <midi-instrument id=\"P{n}-I1\"><pan>{pan}</pan></midi-instrument>\n    \
+            </score-part>",
+            n = i + 1,
+            pan = (voice.pan * 90.0).round()
+        );
+    }
+    xml.push_str("  </part-list>\n");
+
+    for (i, measures) in parts.iter().enumerate() {
+        let _ = writeln!(xml, "  <part id=\"P{}\">", i + 1);
+        let mut tied_from_previous = false;
+        for (m, measure) in measures.iter().enumerate() {
+            let _ = writeln!(xml, "    <measure number=\"{}\">", m + 1);
+            if m == 0 {
+                let clef = match is_bass(measures) {
+                    true => "<sign>F</sign><line>4</line>",
+                    false => "<sign>G</sign><line>2</line>",
+                };
+                let _ = writeln!(
+                    xml,
+                    "      <attributes>\n        <divisions>{divisions}</divisions>\n        \
+                    <key><fifths>0</fifths></key>\n        \
+                    <time><beats>4</beats><beat-type>4</beat-type></time>\n        \
+                    <clef>{clef}</clef>\n      </attributes>"
+                );
+                if i == 0 {
+                    let _ = writeln!(
+                        xml,
+                        "      <direction placement=\"above\">\n        <direction-type>\
+                        <metronome><beat-unit>quarter</beat-unit>\
+                        <per-minute>{tempo}</per-minute></metronome></direction-type>\n        \
+                        <sound tempo=\"{tempo}\"/>\n      </direction>"
+                    );
+                }
+            }
+            for note in measure {
+                write_note(&mut xml, note, tied_from_previous, divisions)?;
+                tied_from_previous = note.tie;
+            }
+            xml.push_str("    </measure>\n");
+        }
+        xml.push_str("  </part>\n");
+    }
+    xml.push_str("</score-partwise>\n");
+
+    fs::write(path, xml)
+        .map_err(|e| format!("Failed to write MusicXML file '{}': {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_voices;
+
+    const TEMPO: u32 = 90;
+    const SAMPLE_RATE: u32 = 44100;
+
+    /// Score of a single part whose measures hold `measures`, in divisions of an eighth note.
+    fn score(measures: &[&str]) -> String {
+        let measures: String = measures
+            .iter()
+            .enumerate()
+            .map(|(i, measure)| {
+                let attributes = match i {
+                    0 => "<attributes><divisions>2</divisions></attributes>",
+                    _ => "",
+                };
+                format!(
+                    "<measure number=\"{}\">{attributes}{measure}</measure>",
+                    i + 1
+                )
+            })
+            .collect();
+        format!(
+            "<score-partwise><part-list><score-part id=\"P1\"/></part-list>\
+            <part id=\"P1\">{measures}</part></score-partwise>"
+        )
+    }
+
+    fn note(step: &str, alter: &str, octave: i32, duration: u32, extra: &str) -> String {
+        format!(
+            "<note>{extra}<pitch><step>{step}</step>{alter}<octave>{octave}</octave></pitch>\
+            <duration>{duration}</duration></note>"
+        )
+    }
+
+    fn read(source: &str) -> Result<Vec<Voice>, String> {
+        read_musicxml(source, &Tuning::new(440.0), TEMPO, SAMPLE_RATE)
+    }
+
+    #[test]
+    fn import_notes() {
+        let measure = [
+            note("C", "", 4, 2, ""),
+            note("E", "", 4, 2, "<chord/>"),
+            String::from("<note><rest/><duration>1</duration></note>"),
+            note("A", "<alter>-1</alter>", 4, 1, "<grace/>"),
+            note("A", "<alter>-1</alter>", 4, 5, "<tie type=\"start\"/>"),
+        ]
+        .concat();
+        let tied = note("A", "<alter>-1</alter>", 4, 2, "<tie type=\"stop\"/>");
+        let voices = read(&score(&[&measure, &tied])).unwrap();
+        assert_eq!(voices.len(), 1);
+        let notes = &voices[0].notes;
+        let names: Vec<Vec<&str>> = notes
+            .iter()
+            .map(|note| note.pitches.iter().map(|p| p.name.as_str()).collect())
+            .collect();
+        assert_eq!(names, [vec!["C4", "E4"], vec![], vec!["Ab4"]]);
+        assert_eq!(notes[0].value, Some(NoteValue::new(1, 4)));
+        assert_eq!(notes[0].num_samples, 29400);
+        assert_eq!(notes[2].value, Some(NoteValue::new(7, 8)));
+    }
+
+    #[test]
+    fn import_voices() {
+        let measure = [
+            note("C", "", 5, 8, ""),
+            String::from("<backup><duration>8</duration></backup>"),
+            note("C", "", 3, 4, ""),
+            note("G", "", 3, 4, ""),
+        ]
+        .concat();
+        let voices = read(&score(&[&measure])).unwrap();
+        assert_eq!(voices.len(), 2);
+        assert_eq!(voices[0].notes.len(), 2);
+        assert_eq!(voices[1].notes.len(), 1);
+        assert_eq!(voices[1].notes[0].pitches[0].name, "C5");
+    }
+
+    #[test]
+    fn import_tempo() {
+        let measure = format!(
+            "<direction><sound tempo=\"180\"/></direction>{}",
+            note("A", "", 4, 2, "")
+        );
+        let notes = &read(&score(&[&measure])).unwrap()[0].notes;
+        assert_eq!(notes[0].num_samples, 14700);
+    }
+
+    #[test]
+    fn import_microtones() {
+        let cents = |alter: &str| {
+            let voices = read(&score(&[&note(
+                "C",
+                &format!("<alter>{alter}</alter>"),
+                4,
+                2,
+                "",
+            )]));
+            let pitch = &voices.unwrap()[0].notes[0].pitches[0];
+            (pitch.name.clone(), pitch.cents.round() as i32)
+        };
+        assert_eq!(cents("0.5"), (String::from("C4"), 50));
+        assert_eq!(cents("-0.5"), (String::from("C4"), -50));
+        assert_eq!(cents("1.5"), (String::from("C#4"), 50));
+        assert_eq!(cents("0.8"), (String::from("C#4"), -20));
+        assert_eq!(cents("-1.3"), (String::from("Cb4"), -30));
+    }
+
+    #[test]
+    fn invalid_alterations() {
+        for alter in ["1e9", "-5", "inf", "NaN", "sharp"] {
+            let measure = note("C", &format!("<alter>{alter}</alter>"), 4, 2, "");
+            let error = read(&score(&[&measure])).unwrap_err();
+            assert!(error.starts_with("Measure 1 of part P1: Invalid alteration"));
+        }
+    }
+
+    #[test]
+    fn invalid_scores() {
+        assert!(read("<score-partwise>").is_err());
+        assert!(read(&score(&["<note><rest/></note>"])).is_err());
+        assert!(read(&score(&["<backup><duration>1</duration></backup>"])).is_err());
+        let measure = note("H", "", 4, 2, "");
+        assert!(read(&score(&[&measure])).is_err());
+    }
+
+    /// Export `source` to a temporary file called `name` and import it again.
+    fn round_trip(name: &str, source: &str) -> (Vec<Voice>, Vec<Voice>) {
+        let tuning = Tuning::new(440.0);
+        let voices = parse_voices(&[source.to_string()], &tuning, TEMPO, SAMPLE_RATE).unwrap();
+        let sequence = Sequence::with_voices(voices.clone(), SAMPLE_RATE);
+        let path =
+            std::env::temp_dir().join(format!("noteseq-{}-{name}.musicxml", std::process::id()));
+        write_musicxml(&path, &sequence, TEMPO).unwrap();
+        let xml = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        (voices, read(&xml).unwrap())
+    }
+
+    /// Semitones and whole cents of the pitches, length and note value of a note.
+    type Summary = (Vec<(i32, i32)>, u128, Option<NoteValue>);
+
+    fn summary(voice: &Voice) -> Vec<Summary> {
+        voice
+            .notes
+            .iter()
+            .map(|note| {
+                let pitches = note
+                    .pitches
+                    .iter()
+                    .map(|pitch| (pitch.semitones, pitch.cents.round() as i32))
+                    .collect();
+                (pitches, note.num_samples, note.value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trip_notes() {
+        let (written, read) = round_trip(
+            "notes",
+            "C4:4 r:8 Eb4:8. [G4 C5]:16 {3:2 A4:8 B4:8 C5:8} C#5:2~C#5:8 D5:8 | \
+            Bb2:1 {5:4 C3:16 D3:16 E3:16 F3:16 G3:16} A+3:4 Cd3:4 F#3:4",
+        );
+        assert_eq!(read.len(), 2);
+        for (read, written) in read.iter().zip(&written) {
+            assert_eq!(summary(read), summary(written));
+        }
+    }
+
+    #[test]
+    fn round_trip_spelling() {
+        let (_, read) = round_trip("spelling", "Cb4:4 B#3:4 Fx4:4 Ebb5:4");
+        let names: Vec<&str> = read[0]
+            .notes
+            .iter()
+            .map(|note| note.pitches[0].name.as_str())
+            .collect();
+        assert_eq!(names, ["Cb4", "B#3", "F##4", "Ebb5"]);
+    }
+
+    #[test]
+    fn export_instruments() {
+        let tuning = Tuning::new(440.0);
+        let voices = parse_voices(
+            &[String::from("C4:1 | @pan:1 E4:1")],
+            &tuning,
+            TEMPO,
+            SAMPLE_RATE,
+        )
+        .unwrap();
+        let path = std::env::temp_dir().join(format!(
+            "noteseq-{}-instruments.musicxml",
+            std::process::id()
+        ));
+        write_musicxml(&path, &Sequence::with_voices(voices, SAMPLE_RATE), TEMPO).unwrap();
+        let xml = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let document = Document::parse_with_options(
+            &xml,
+            ParsingOptions {
+                allow_dtd: true,
+                ..ParsingOptions::default()
+            },
+        )
+        .unwrap();
+        let parts: Vec<Node> = document
+            .descendants()
+            .filter(|node| node.has_tag_name("score-part"))
+            .collect();
+        assert_eq!(parts.len(), 2);
+        for part in parts {
+            let instrument = child(part, "score-instrument").unwrap();
+            let midi_instrument = child(part, "midi-instrument").unwrap();
+            assert_eq!(instrument.attribute("id"), midi_instrument.attribute("id"));
+            assert!(child_text(instrument, "instrument-name").is_some());
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use crate::note::{Note, Pitch};
+use crate::sequence::Voice;
+use crate::value::NoteValue;
+
+/// Base-2 logarithm of the divisor of the shortest note value that can be notated, a 1024th.
+const SHORTEST: u32 = 10;
+
+/// Most dots a notated value can have.
+const MAX_DOTS: u32 = 3;
+
+/// Notated length of a note: a power of two fraction of a whole note with dots, optionally
+/// played in a tuplet.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct NotatedValue {
+    /// Base-2 logarithm of the divisor of a whole note, e.g. 2 for a quarter note.
+    pub divisor_log: u32,
+    pub dots: u32,
+    /// Number of notes played in the time of a number of normal notes, e.g. (3, 2) for a
+    /// triplet.
+    pub tuplet: Option<(u64, u64)>,
+    /// Length of the note as played, as a fraction of a whole note.
+    pub value: NoteValue,
+}
+
+/// Note or rest of a notated measure.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct NotatedNote {
+    /// Pitches of the note, none for a rest.
+    pub pitches: Vec<Pitch>,
+    pub value: NotatedValue,
+    /// Whether the note is tied to the next one.
+    pub tie: bool,
+}
+
+/// Length of the measures of notated voices, which are in 4/4.
+pub(crate) fn measure_length() -> NoteValue {
+    NoteValue::whole()
+}
+
+/// Split `value` into notatable values to be tied together, longest first. A value that is
+/// not a sum of power of two fractions of a whole note is played in a tuplet, e.g. a third of
+/// a whole note is a half note of a 3:2 triplet.
+pub(crate) fn split_value(value: NoteValue) -> Result<Vec<NotatedValue>, String> {
+    let too_short = || {
+        format!(
+            "Note value {}/{} cannot be notated",
+            value.numerator(),
+            value.denominator()
+        )
+    };
+    let denominator = value.denominator();
+    let odd = denominator >> denominator.trailing_zeros();
+    let tuplet = (odd > 1).then(|| (odd, 1 << odd.ilog2()));
+    let (written, scale) = match tuplet {
+        Some((actual, normal)) => (
+            value
+                .checked_mul(NoteValue::new(actual, normal))
+                .ok_or_else(too_short)?,
+            NoteValue::new(normal, actual),
+        ),
+        None => (value, NoteValue::whole()),
+    };
+
+    let mut values = Vec::new();
+    let mut remaining = written;
+    while remaining > NoteValue::zero() {
+        // Longest power of two fraction of a whole note that fits, at most a whole note
+        let mut divisor_log = 0;
+        while NoteValue::new(1, 1 << divisor_log) > remaining {
+            divisor_log += 1;
+            if divisor_log > SHORTEST {
+                return Err(too_short());
+            }
+        }
+        let dotted = |dots: u32| {
+            NoteValue::new(1, 1 << divisor_log)
+                .checked_mul(NoteValue::dotted(dots))
+                .unwrap()
+        };
+        let mut dots = 0;
+        while dots < MAX_DOTS && divisor_log + dots < SHORTEST && dotted(dots + 1) <= remaining {
+            dots += 1;
+        }
+        remaining = remaining.checked_sub(dotted(dots)).unwrap();
+        values.push(NotatedValue {
+            divisor_log,
+            dots,
+            tuplet,
+            value: dotted(dots).checked_mul(scale).ok_or_else(too_short)?,
+        });
+    }
+    Ok(values)
+}
+
+/// Values that the part of a note value derived from a length in samples beyond its whole
+/// notes is rounded to: zero, a whole note, and every plain, single dotted and triplet value.
+fn snap_values() -> Vec<NoteValue> {
+    let mut values = vec![NoteValue::zero(), NoteValue::whole()];
+    for divisor_log in 1..=SHORTEST {
+        let plain = NoteValue::new(1, 1 << divisor_log);
+        values.push(plain);
+        if divisor_log < SHORTEST {
+            values.push(NoteValue::new(3, 1 << (divisor_log + 1)));
+        }
+        values.push(NoteValue::new(2, 3 << divisor_log));
+    }
+    values
+}
+
+/// Note value of a note, rounded to the nearest plain, dotted or triplet value if it was
+/// created from a length in samples, so that it can be notated without a chain of short ties.
+fn get_note_value(note: &Note, tempo: u32, sample_rate: u32) -> Result<NoteValue, String> {
+    if let Some(value) = note.value {
+        return Ok(value);
+    }
+    let wholes = note.num_samples as f64 * tempo as f64 / (240.0 * sample_rate as f64);
+    let (whole_notes, remainder) = (wholes.trunc(), wholes.fract());
+    let to_f64 = |value: &NoteValue| value.numerator() as f64 / value.denominator() as f64;
+    let snapped = snap_values()
+        .into_iter()
+        // A note is never rounded away entirely
+        .filter(|value| whole_notes > 0.0 || *value > NoteValue::zero())
+        .min_by(|a, b| {
+            (to_f64(a) - remainder)
+                .abs()
+                .total_cmp(&(to_f64(b) - remainder).abs())
+        })
+        .unwrap();
+    (whole_notes < u64::MAX as f64)
+        .then(|| NoteValue::new(whole_notes as u64, 1).checked_add(snapped))
+        .flatten()
+        .ok_or_else(|| String::from("Note is too long to notate"))
+}
+
+/// Pitches of a note, spelled with sharps if the note was created from frequencies.
+fn get_pitches(note: &Note) -> Vec<Pitch> {
+    match note.pitches.is_empty() {
+        true => note
+            .frequencies
+            .iter()
+            .map(|&frequency| Pitch::from_frequency(frequency))
+            .collect(),
+        false => note.pitches.clone(),
+    }
+}
+
+/// Split the notes of `voice` into measures, where notes crossing a bar line or lasting for
+/// values that cannot be written as a single note are tied.
+pub(crate) fn notate_voice(
+    voice: &Voice,
+    tempo: u32,
+    sample_rate: u32,
+) -> Result<Vec<Vec<NotatedNote>>, String> {
+    let mut measures = vec![Vec::new()];
+    let mut position = NoteValue::zero();
+    for note in &voice.notes {
+        let pitches = get_pitches(note);
+        let mut remaining = get_note_value(note, tempo, sample_rate)?;
+        while remaining > NoteValue::zero() {
+            let space = measure_length().checked_sub(position).unwrap();
+            let piece = remaining.min(space);
+            remaining = remaining.checked_sub(piece).unwrap();
+            position = position.checked_add(piece).unwrap();
+
+            let values = split_value(piece)?;
+            let last = values.len() - 1;
+            let measure = measures.last_mut().unwrap();
+            for (i, value) in values.into_iter().enumerate() {
+                measure.push(NotatedNote {
+                    pitches: pitches.clone(),
+                    value,
+                    tie: !pitches.is_empty() && (i < last || remaining > NoteValue::zero()),
+                });
+            }
+            if position == measure_length() {
+                measures.push(Vec::new());
+                position = NoteValue::zero();
+            }
+        }
+    }
+    if measures.len() > 1 && measures.last().is_some_and(Vec::is_empty) {
+        measures.pop();
+    }
+    Ok(measures)
+}
+
+/// Whether a voice is low enough to be notated in the bass clef, i.e. its average pitch is
+/// below middle C.
+pub(crate) fn is_bass(measures: &[Vec<NotatedNote>]) -> bool {
+    let semitones: Vec<i32> = measures
+        .iter()
+        .flatten()
+        .flat_map(|note| note.pitches.iter().map(|pitch| pitch.semitones))
+        .collect();
+    !semitones.is_empty() && semitones.iter().sum::<i32>() < -9 * semitones.len() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::Voice;
+
+    const TEMPO: u32 = 120;
+    const SAMPLE_RATE: u32 = 48000;
+
+    /// Note of A4 lasting `num_samples`, without a note value.
+    fn note(num_samples: u128) -> Note {
+        Note::with_num_samples(440.0, 0.8, SAMPLE_RATE, num_samples).unwrap()
+    }
+
+    fn value(num_samples: u128) -> NoteValue {
+        get_note_value(&note(num_samples), TEMPO, SAMPLE_RATE).unwrap()
+    }
+
+    #[test]
+    fn split_values() {
+        let split = |value| {
+            split_value(value)
+                .unwrap()
+                .into_iter()
+                .map(|value| (value.divisor_log, value.dots, value.tuplet))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(split(NoteValue::new(1, 4)), [(2, 0, None)]);
+        assert_eq!(split(NoteValue::new(7, 16)), [(2, 2, None)]);
+        assert_eq!(split(NoteValue::new(5, 8)), [(1, 0, None), (3, 0, None)]);
+        assert_eq!(split(NoteValue::new(1, 12)), [(3, 0, Some((3, 2)))]);
+        assert_eq!(split(NoteValue::new(1, 20)), [(4, 0, Some((5, 4)))]);
+        assert!(split_value(NoteValue::new(1, 4096)).is_err());
+    }
+
+    #[test]
+    fn snapped_values() {
+        // A quarter note at 120 bpm and 48 kHz lasts 24000 samples
+        assert_eq!(value(24000), NoteValue::new(1, 4));
+        assert_eq!(value(24100), NoteValue::new(1, 4));
+        assert_eq!(value(36000), NoteValue::new(3, 8));
+        assert_eq!(value(16000), NoteValue::new(1, 6));
+        assert_eq!(value(19200), NoteValue::new(3, 16));
+        assert_eq!(value(96000 * 2 + 12000), NoteValue::new(17, 8));
+        assert_eq!(value(96000 * 3 + 10), NoteValue::new(3, 1));
+        assert_eq!(value(1), NoteValue::new(1, 1536));
+    }
+
+    #[test]
+    fn notated_sample_lengths() {
+        let voice = Voice::new((0..5).map(|_| note(19200)).collect());
+        let measures = notate_voice(&voice, TEMPO, SAMPLE_RATE).unwrap();
+        let notes: Vec<&NotatedNote> = measures.iter().flatten().collect();
+        assert!(notes.iter().all(|note| note.value.tuplet.is_none()));
+        let total = notes
+            .iter()
+            .try_fold(NoteValue::zero(), |total, note| {
+                total.checked_add(note.value.value)
+            })
+            .unwrap();
+        assert_eq!(total, NoteValue::new(15, 16));
+        assert_eq!(measures.len(), 1);
+    }
+
+    #[test]
+    fn notated_measures() {
+        let mut notes = vec![note(0); 2];
+        notes[0].value = Some(NoteValue::new(3, 4));
+        notes[1].value = Some(NoteValue::new(1, 2));
+        let measures = notate_voice(&Voice::new(notes), TEMPO, SAMPLE_RATE).unwrap();
+        assert_eq!(measures.len(), 2);
+        assert_eq!(measures[0].len(), 2);
+        assert!(!measures[0][0].tie);
+        assert!(measures[0][1].tie);
+        assert_eq!(measures[1].len(), 1);
+        assert_eq!(measures[1][0].value.value, NoteValue::new(1, 4));
+    }
+}
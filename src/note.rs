@@ -0,0 +1,451 @@
+use regex::Regex;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::tuning::Tuning;
+use crate::value::NoteValue;
+use crate::waveform::Waveform;
+use crate::REFERENCE_OCTAVE;
+
+/// Amplitude of notes parsed from a sequence.
+pub const DEFAULT_AMPLITUDE: f32 = 0.8;
+
+/// MIDI note number of A4.
+pub const A4_MIDI_KEY: i32 = 69;
+
+/// Lowest and highest octave of a note name, from the octave of the lowest MIDI note C-1 to
+/// the octave above the highest MIDI note G9.
+pub const MIN_OCTAVE: i32 = -1;
+pub const MAX_OCTAVE: i32 = 10;
+
+/// Lowest and highest frequency of a pitch in Hz, from just below C-1 to the upper limit of
+/// hearing.
+pub const MIN_FREQUENCY: f32 = 8.0;
+pub const MAX_FREQUENCY: f32 = 20_000.0;
+
+/// Number of samples spanned by `duration` at the given sample rate.
+pub fn duration_to_samples(sample_rate: u32, duration: Duration) -> u128 {
+    // Order of operations is important here to avoid truncation
+    sample_rate as u128 * duration.as_nanos() / 1_000_000_000
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Note {
+    /// Frequencies sounding simultaneously, i.e. one for a single note, several for a chord
+    /// and none for a rest.
+    pub frequencies: Vec<f32>,
+    /// Pitches the frequencies were written as, or empty if the note was created from
+    /// frequencies.
+    pub pitches: Vec<Pitch>,
+    pub amplitude: f32,
+    /// Waveform of the note, or `None` to use the waveform of the voice.
+    pub waveform: Option<Waveform>,
+    /// Stereo position from -1 (left) to 1 (right), or `None` to use the pan of the voice.
+    pub pan: Option<f32>,
+    /// Length of the note in samples. A length of zero holds the note indefinitely.
+    pub num_samples: u128,
+    /// Note value the note was written with, or `None` if the note was created from a length
+    /// in samples.
+    pub value: Option<NoteValue>,
+}
+
+impl Note {
+    pub fn new(
+        frequency: f32,
+        amplitude: f32,
+        sample_rate: u32,
+        duration: Duration,
+    ) -> Result<Self, String> {
+        Note::with_num_samples(
+            frequency,
+            amplitude,
+            sample_rate,
+            duration_to_samples(sample_rate, duration),
+        )
+    }
+
+    pub fn with_num_samples(
+        frequency: f32,
+        amplitude: f32,
+        sample_rate: u32,
+        num_samples: u128,
+    ) -> Result<Self, String> {
+        Note::chord(vec![frequency], amplitude, sample_rate, num_samples)
+    }
+
+    /// Create a chord where every frequency is played at `amplitude`.
+    pub fn chord(
+        frequencies: Vec<f32>,
+        amplitude: f32,
+        sample_rate: u32,
+        num_samples: u128,
+    ) -> Result<Self, String> {
+        match frequencies
+            .iter()
+            .find(|&&frequency| frequency > sample_rate as f32 / 2.0)
+        {
+            Some(frequency) => Err(format!(
+                "Cannot create note of frequency {frequency} Hz \
+                when the sample rate is {sample_rate} Hz, \
+                since it exceeds the Nyquist frequency of {nyquist} Hz.",
+                nyquist = sample_rate / 2
+            )),
+            None => Ok(Note {
+                frequencies,
+                pitches: Vec::new(),
+                amplitude,
+                waveform: None,
+                pan: None,
+                num_samples,
+                value: None,
+            }),
+        }
+    }
+
+    /// Create a silent note spanning `num_samples` samples.
+    pub fn rest(num_samples: u128) -> Self {
+        Note {
+            frequencies: Vec::new(),
+            pitches: Vec::new(),
+            amplitude: 0.0,
+            waveform: None,
+            pan: None,
+            num_samples,
+            value: None,
+        }
+    }
+
+    pub fn is_rest(&self) -> bool {
+        self.frequencies.is_empty()
+    }
+}
+
+/// A pitch written as a note name, e.g. `C#4`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pitch {
+    /// Note name as written in the sequence.
+    pub name: String,
+    /// Distance in semitones from A4.
+    pub semitones: i32,
+    /// Deviation from the semitone in cents, from quarter-tone accidentals and cent offsets.
+    pub cents: f32,
+}
+
+impl Pitch {
+    /// Pitch of a note letter raised by `accidental` semitones in `octave`, spelled with `#`
+    /// or `b` accidentals, e.g. `F`, 1 and 5 is `F#5`.
+    pub fn new(letter: char, accidental: i32, octave: i32) -> Result<Self, String> {
+        let letter = letter.to_ascii_uppercase();
+        let semitone_distance: i32 = match letter {
+            'C' => -9,
+            'D' => -7,
+            'E' => -5,
+            'F' => -4,
+            'G' => -2,
+            'A' => 0,
+            'B' => 2,
+            unknown => return Err(format!("Unknown note: {unknown}")),
+        };
+        let symbol = if accidental < 0 { "b" } else { "#" };
+        Ok(Pitch {
+            name: format!(
+                "{letter}{}{octave}",
+                symbol.repeat(accidental.unsigned_abs() as usize)
+            ),
+            semitones: semitone_distance + 12 * (octave - REFERENCE_OCTAVE) + accidental,
+            cents: 0.0,
+        })
+    }
+
+    /// Pitch `semitones` from A4, spelled with sharps, e.g. 3 is `C5`.
+    pub fn from_semitones(semitones: i32) -> Self {
+        let (letter, accidental, octave) = get_sharp_spelling(semitones);
+        Pitch::new(letter, accidental, octave).unwrap()
+    }
+
+    /// Nearest pitch to `frequency` in equal temperament where A4 is 440 Hz, spelled with
+    /// sharps and deviating by the cents that remain, e.g. 445 Hz is `A4` and 19.56 cents.
+    pub fn from_frequency(frequency: f32) -> Self {
+        let distance = 12.0 * (frequency / 440.0).log2();
+        let semitones = distance.round();
+        Pitch {
+            cents: 100.0 * (distance - semitones),
+            ..Pitch::from_semitones(semitones as i32)
+        }
+    }
+
+    /// Pitch of a MIDI note number, spelled with sharps, e.g. 61 is `C#4`.
+    pub fn from_midi_key(key: u8) -> Self {
+        Pitch::from_semitones(key as i32 - A4_MIDI_KEY)
+    }
+
+    /// Note letter, accidental in semitones and octave of the pitch as written, e.g. `Bb3` is
+    /// `B`, -1 and 3. Pitches not written as note names are spelled with sharps. Quarter tones
+    /// and cent offsets are left out, see [`Pitch::cents`].
+    pub fn spelling(&self) -> (char, i32, i32) {
+        let mut chars = self.name.chars();
+        match chars.next() {
+            Some(letter @ ('A'..='G' | 'a'..='g')) => {
+                let accidental = chars
+                    .take_while(|c| "#bx+d".contains(*c))
+                    .map(|c| match c {
+                        '#' => 1,
+                        'x' => 2,
+                        'b' => -1,
+                        _ => 0,
+                    })
+                    .sum::<i32>();
+                let letter = letter.to_ascii_uppercase();
+                let natural = Pitch::new(letter, 0, REFERENCE_OCTAVE).unwrap().semitones;
+                let octave =
+                    REFERENCE_OCTAVE + (self.semitones - natural - accidental).div_euclid(12);
+                (letter, accidental, octave)
+            }
+            _ => get_sharp_spelling(self.semitones),
+        }
+    }
+
+    /// Frequency of the pitch in `tuning`.
+    pub fn frequency(&self, tuning: &Tuning) -> Result<f32, String> {
+        Ok(tuning.frequency(self.semitones)? * 2f32.powf(self.cents / 1200.0))
+    }
+
+    /// MIDI note number of the pitch, where A4 is 69, or `None` if it is outside the MIDI
+    /// range of 0-127.
+    pub fn midi_key(&self) -> Option<u8> {
+        (self.semitones + A4_MIDI_KEY)
+            .try_into()
+            .ok()
+            .filter(|&key| key < 128)
+    }
+}
+
+impl FromStr for Pitch {
+    type Err = String;
+
+    fn from_str(note_name: &str) -> Result<Self, Self::Err> {
+        fn count_chars(string: &str, c: char) -> i32 {
+            string
+                .chars()
+                .filter(|x| *x == c)
+                .count()
+                .try_into()
+                .unwrap()
+        }
+        if let Some(key) = note_name.strip_prefix('m') {
+            return match key.parse::<u8>() {
+                Ok(key) if key < 128 => Ok(Pitch {
+                    name: note_name.to_string(),
+                    ..Pitch::from_midi_key(key)
+                }),
+                _ => Err(format!(
+                    "Invalid MIDI note number: {key}. Must be a number from 0-127, e.g. m60."
+                )),
+            };
+        }
+
+        let re = Regex::new(
+            r"^(?P<note>[a-gA-G])(?P<accidental>[#bx+d]*)(?P<octave>-?[0-9]+)?(?P<cents>[+-][0-9]+(\.[0-9]+)?c)?$",
+        )
+        .unwrap();
+        let captures = match re.captures(note_name) {
+            Some(captures) => captures,
+            None => {
+                return Err(format!(
+                    "Invalid note: {note_name}. Must be letter from A-G (case insensitive), \
+                optionally followed by accidentals # (sharp), b (flat), x (double sharp), \
+                + (quarter-tone sharp) or d (quarter-tone flat), an octave number and an \
+                offset in cents. E.g. C#4, C-1 or A4+15c. A pitch can also be a MIDI note \
+                number, e.g. m60."
+                ))
+            }
+        };
+
+        let note = captures.name("note").unwrap().as_str();
+        let accidental = captures.name("accidental").unwrap().as_str();
+        let semitone_offset = count_chars(accidental, '#') + 2 * count_chars(accidental, 'x')
+            - count_chars(accidental, 'b');
+        let quarter_tones = count_chars(accidental, '+') - count_chars(accidental, 'd');
+
+        let octave_num = match captures.name("octave") {
+            None => REFERENCE_OCTAVE,
+            Some(octave) => match octave.as_str().parse::<i32>() {
+                Ok(octave) if (MIN_OCTAVE..=MAX_OCTAVE).contains(&octave) => octave,
+                _ => {
+                    return Err(format!(
+                        "Octave {} of note {note_name} is outside the range \
+                        {MIN_OCTAVE} to {MAX_OCTAVE}",
+                        octave.as_str()
+                    ))
+                }
+            },
+        };
+
+        let cents = match captures.name("cents") {
+            Some(cents) => {
+                let cents = cents.as_str().trim_end_matches('c');
+                cents
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|cents| cents.is_finite())
+                    .ok_or_else(|| format!("Invalid offset of {cents} cents in note {note_name}"))?
+            }
+            None => 0.0,
+        };
+
+        let letter = note.chars().next().unwrap();
+        Ok(Pitch {
+            name: note_name.to_string(),
+            cents: 50.0 * quarter_tones as f32 + cents,
+            ..Pitch::new(letter, semitone_offset, octave_num)?
+        })
+    }
+}
+
+/// Note letter, accidental and octave of the pitch `semitones` from A4, spelled with sharps.
+fn get_sharp_spelling(semitones: i32) -> (char, i32, i32) {
+    const SPELLINGS: [(char, i32); 12] = [
+        ('C', 0),
+        ('C', 1),
+        ('D', 0),
+        ('D', 1),
+        ('E', 0),
+        ('F', 0),
+        ('F', 1),
+        ('G', 0),
+        ('G', 1),
+        ('A', 0),
+        ('A', 1),
+        ('B', 0),
+    ];
+    let key = semitones + A4_MIDI_KEY;
+    let (letter, accidental) = SPELLINGS[key.rem_euclid(12) as usize];
+    (letter, accidental, key.div_euclid(12) - 1)
+}
+
+pub fn get_frequency_from_note(note_name: &str, tuning: &Tuning) -> Result<f32, String> {
+    note_name.parse::<Pitch>()?.frequency(tuning)
+}
+
+pub fn get_note(
+    note_name: &str,
+    tuning: &Tuning,
+    sample_rate: u32,
+    num_samples: u128,
+) -> Result<Note, String> {
+    Note::with_num_samples(
+        get_frequency_from_note(note_name, tuning)?,
+        DEFAULT_AMPLITUDE,
+        sample_rate,
+        num_samples,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pitch(name: &str) -> Pitch {
+        name.parse()
+            .unwrap_or_else(|e| panic!("'{name}' should parse: {e}"))
+    }
+
+    #[test]
+    fn note_names() {
+        assert_eq!(pitch("A4").semitones, 0);
+        assert_eq!(pitch("a").semitones, 0);
+        assert_eq!(pitch("C4").semitones, -9);
+        assert_eq!(pitch("c#4").semitones, -8);
+        assert_eq!(pitch("Bb3").semitones, -11);
+        assert_eq!(pitch("Cx4").semitones, -7);
+        assert_eq!(pitch("Cbb4").semitones, -11);
+        assert_eq!(pitch("C5").semitones, 3);
+        assert_eq!(pitch("Eb5").name, "Eb5");
+    }
+
+    #[test]
+    fn microtones() {
+        assert_eq!(pitch("A+4").cents, 50.0);
+        assert_eq!(pitch("Ad4").cents, -50.0);
+        assert_eq!(pitch("A#+4").cents, 50.0);
+        assert_eq!(pitch("A#+4").semitones, 1);
+        assert_eq!(pitch("A4+15c").cents, 15.0);
+        assert_eq!(pitch("A4-15.5c").cents, -15.5);
+        assert_eq!(pitch("Ad4+20c").cents, -30.0);
+        assert_eq!(pitch("A+15c").semitones, 0);
+    }
+
+    #[test]
+    fn invalid_note_names() {
+        for name in [
+            "", "H4", "4", "#4", "C4#", "Cs4", "C4.5", "C4+c", "C4+1.c", "C4 ", "é",
+        ] {
+            assert!(name.parse::<Pitch>().is_err(), "'{name}' should be invalid");
+        }
+    }
+
+    #[test]
+    fn invalid_cents() {
+        let cents = "9".repeat(40);
+        let error = format!("A4+{cents}c").parse::<Pitch>().unwrap_err();
+        assert_eq!(
+            error,
+            format!("Invalid offset of +{cents} cents in note A4+{cents}c")
+        );
+    }
+
+    #[test]
+    fn frequencies() {
+        let tuning = Tuning::new(440.0);
+        assert_eq!(pitch("A4").frequency(&tuning), Ok(440.0));
+        assert_eq!(pitch("A5").frequency(&tuning), Ok(880.0));
+        assert!((pitch("C4").frequency(&tuning).unwrap() - 261.6256).abs() < 1e-3);
+        assert!((pitch("A+4").frequency(&tuning).unwrap() - 452.893).abs() < 1e-3);
+        assert_eq!(pitch("A4").frequency(&Tuning::new(432.0)), Ok(432.0));
+    }
+
+    #[test]
+    fn spellings() {
+        assert_eq!(pitch("Bb3").spelling(), ('B', -1, 3));
+        assert_eq!(pitch("Cb4").spelling(), ('C', -1, 4));
+        assert_eq!(pitch("B#3").spelling(), ('B', 1, 3));
+        assert_eq!(pitch("Ed4").spelling(), ('E', 0, 4));
+    }
+
+    #[test]
+    fn from_frequency() {
+        let pitch = Pitch::from_frequency(445.0);
+        assert_eq!(pitch.name, "A4");
+        assert!((pitch.cents - 19.56).abs() < 0.01);
+        assert_eq!(Pitch::from_frequency(261.63).name, "C4");
+        assert_eq!(Pitch::from_frequency(277.18).name, "C#4");
+    }
+
+    #[test]
+    fn octaves() {
+        assert_eq!(pitch("C-1").semitones, -69);
+        assert_eq!(pitch("G9").semitones, 58);
+        assert_eq!(pitch("C10").semitones, 63);
+        assert_eq!(pitch("B10").semitones, 74);
+        assert_eq!(pitch("C-1").spelling(), ('C', 0, -1));
+        assert_eq!(pitch("B#-1").spelling(), ('B', 1, -1));
+        assert_eq!(Pitch::from_semitones(-58).spelling(), ('B', 0, -1));
+        for name in ["C11", "C-2", "C100", "C-10", "C--1"] {
+            assert!(name.parse::<Pitch>().is_err(), "'{name}' should be invalid");
+        }
+    }
+
+    #[test]
+    fn midi_keys() {
+        let middle_c = pitch("m60");
+        assert_eq!(middle_c.name, "m60");
+        assert_eq!(middle_c.semitones, -9);
+        assert_eq!(pitch("m61").spelling(), ('C', 1, 4));
+        assert_eq!(pitch("m0").midi_key(), Some(0));
+        assert_eq!(pitch("m127").midi_key(), Some(127));
+        assert_eq!(pitch("C10").midi_key(), None);
+        for name in ["m128", "m-1", "m", "m60.0", "m 60"] {
+            assert!(name.parse::<Pitch>().is_err(), "'{name}' should be invalid");
+        }
+    }
+}
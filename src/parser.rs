@@ -0,0 +1,993 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+use crate::lexer::{tokenize, Span, Token, TokenKind};
+use crate::note::{Note, Pitch, DEFAULT_AMPLITUDE, MAX_FREQUENCY, MIN_FREQUENCY};
+use crate::sequence::Voice;
+use crate::tuning::Tuning;
+use crate::value::NoteValue;
+use crate::waveform::Waveform;
+
+#[derive(Debug)]
+pub struct ArgumentParseError {
+    msg: String,
+    /// Location of the error within the source of the sequence.
+    span: Option<Span>,
+    /// Index of the offending token, counting from 1.
+    token: Option<usize>,
+    /// Rendered location and snippet of the source underlining the error.
+    context: Option<String>,
+}
+
+impl ArgumentParseError {
+    pub fn new(msg: &str) -> Self {
+        ArgumentParseError {
+            msg: msg.to_string(),
+            span: None,
+            token: None,
+            context: None,
+        }
+    }
+
+    /// Locate the error at `span`, unless it has already been located more precisely.
+    fn at(mut self, span: Span, token: usize) -> Self {
+        self.span.get_or_insert(span);
+        self.token.get_or_insert(token);
+        self
+    }
+
+    /// Locate the error at `span` of a source that is not split into tokens.
+    pub(crate) fn at_span(mut self, span: Span) -> Self {
+        self.span.get_or_insert(span);
+        self
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    pub fn token(&self) -> Option<usize> {
+        self.token
+    }
+
+    /// Render the line of `source` containing the error, with the span underlined by carets.
+    fn render_context(&mut self, source: &str) {
+        let Some(span) = self.span else {
+            return;
+        };
+        let line_start = source[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[span.start..]
+            .find('\n')
+            .map_or(source.len(), |i| span.start + i);
+        let line_num = source[..span.start].matches('\n').count() + 1;
+        let column = source[line_start..span.start].chars().count() + 1;
+        let width = source[span.start..span.end.min(line_end)]
+            .chars()
+            .count()
+            .max(1);
+        let line = source[line_start..line_end].replace('\t', " ");
+
+        let location = match self.token {
+            Some(token) => format!("token {token}, line {line_num}, column {column}"),
+            None => format!("line {line_num}, column {column}"),
+        };
+        let gutter = " ".repeat(line_num.to_string().len());
+        self.context = Some(format!(
+            "{gutter}--> {location}\n\
+            {gutter} |\n\
+            {line_num} | {line}\n\
+            {gutter} | {}{}",
+            " ".repeat(column - 1),
+            "^".repeat(width)
+        ));
+    }
+}
+
+impl fmt::Display for ArgumentParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.context {
+            Some(context) => write!(f, "{}\n{}", self.msg, context),
+            None => write!(f, "{}", self.msg),
+        }
+    }
+}
+
+impl Error for ArgumentParseError {
+    fn description(&self) -> &str {
+        &self.msg
+    }
+}
+
+/// Every error found while parsing a sequence.
+#[derive(Debug)]
+pub struct ParseErrors {
+    errors: Vec<ArgumentParseError>,
+}
+
+impl ParseErrors {
+    pub(crate) fn new(mut errors: Vec<ArgumentParseError>, source: &str) -> Self {
+        for error in &mut errors {
+            error.render_context(source);
+        }
+        ParseErrors { errors }
+    }
+
+    pub fn errors(&self) -> &[ArgumentParseError] {
+        &self.errors
+    }
+}
+
+impl fmt::Display for ParseErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let messages: Vec<String> = self.errors.iter().map(|e| e.to_string()).collect();
+        write!(f, "{}", messages.join("\n\n"))
+    }
+}
+
+impl Error for ParseErrors {}
+
+/// Parse a note value into a fraction of a whole note.
+///
+/// The note value is the divisor of a whole note, optionally followed by any number of dots.
+/// Each dot adds half of the previously added length, so `4.` is a quarter plus an eighth and
+/// `8..` is an eighth plus a sixteenth plus a thirty-second. A trailing `t` makes the value a
+/// triplet, i.e. two thirds of its normal length, so `8t` is a triplet eighth.
+pub fn parse_note_value(s: &str) -> Result<NoteValue, ArgumentParseError> {
+    let (dotted, triplet) = match s.strip_suffix('t') {
+        Some(dotted) => (dotted, true),
+        None => (s, false),
+    };
+    let value = dotted.trim_end_matches('.');
+    let dots = dotted.len() - value.len();
+
+    if value.is_empty() {
+        return Err(ArgumentParseError::new(
+            format!("Note value '{s}' is missing a number").as_str(),
+        ));
+    }
+    if value.contains('.') {
+        return Err(ArgumentParseError::new(
+            format!("Invalid note value '{s}': dots must follow the number, e.g. 4. or 8..")
+                .as_str(),
+        ));
+    }
+
+    let num = match value.parse::<u32>() {
+        Ok(n) => n,
+        Err(e) => {
+            return Err(ArgumentParseError::new(
+                format!("Failed parse integer from string '{value}': {e}").as_str(),
+            ))
+        }
+    };
+
+    // Check if number is power of two
+    if (num != 0) && (num & (num - 1)) == 0 {
+        if dots > 16 {
+            return Err(ArgumentParseError::new(
+                format!("Note value '{s}' has too many dots").as_str(),
+            ));
+        }
+        let triplet_scale = match triplet {
+            true => NoteValue::new(2, 3),
+            false => NoteValue::whole(),
+        };
+        NoteValue::new(1, num as u64)
+            .checked_mul(NoteValue::dotted(dots as u32))
+            .and_then(|value| value.checked_mul(triplet_scale))
+            .ok_or_else(|| {
+                ArgumentParseError::new(format!("Note value '{s}' is too short").as_str())
+            })
+    } else {
+        Err(ArgumentParseError::new(
+            format!("Note duration {num} was not a power of two").as_str(),
+        ))
+    }
+}
+
+/// Parse a note value into a duration at the given tempo. See [`parse_note_value`] for the
+/// accepted syntax.
+pub fn parse_duration(s: &str, tempo: u32) -> Result<Duration, ArgumentParseError> {
+    Ok(parse_note_value(s)?.to_duration(tempo))
+}
+
+/// Parse the `n:m` ratio of a tuplet group, meaning n notes in the time of m, into the factor
+/// that scales the note values inside the group.
+fn parse_tuplet_ratio(s: &str) -> Result<NoteValue, ArgumentParseError> {
+    let invalid = || {
+        ArgumentParseError::new(
+            format!("Invalid tuplet ratio '{s}'. Must be on the format n:m, e.g. {{3:2").as_str(),
+        )
+    };
+    let (n, m) = s.split_once(':').ok_or_else(invalid)?;
+    let n = n.parse::<u64>().map_err(|_| invalid())?;
+    let m = m.parse::<u64>().map_err(|_| invalid())?;
+    if n == 0 || m == 0 {
+        return Err(invalid());
+    }
+    Ok(NoteValue::new(m, n))
+}
+
+/// Check whether the pitch part of a token denotes a rest, i.e. `r` or `R`.
+fn is_rest(pitch: &str) -> bool {
+    pitch.eq_ignore_ascii_case("r")
+}
+
+/// Parse the pitch part of a token into a pitch and its frequency in `tuning`. Besides a note
+/// name, the pitch can be a frequency in Hz, e.g. `440hz`, which is played regardless of the
+/// tuning, or a degree of the Scala scale of the tuning, e.g. `s7`. Both are spelled as the
+/// nearest pitch in equal temperament. The frequency must be within the audible range.
+fn parse_pitch(pitch: &str, tuning: &Tuning) -> Result<(Pitch, f32), String> {
+    let (pitch, frequency) = get_pitch(pitch, tuning)?;
+    match (MIN_FREQUENCY..=MAX_FREQUENCY).contains(&frequency) {
+        true => Ok((pitch, frequency)),
+        false => Err(format!(
+            "Pitch {} has the frequency {frequency} Hz, which is outside the audible range of \
+            {MIN_FREQUENCY}-{MAX_FREQUENCY} Hz",
+            pitch.name
+        )),
+    }
+}
+
+/// Pitch and frequency of the pitch part of a token, see [`parse_pitch`].
+fn get_pitch(pitch: &str, tuning: &Tuning) -> Result<(Pitch, f32), String> {
+    let frequency = if let Some(frequency) = pitch
+        .strip_suffix("hz")
+        .or_else(|| pitch.strip_suffix("Hz"))
+    {
+        match frequency.parse::<f32>() {
+            Ok(frequency) if frequency.is_finite() && frequency > 0.0 => frequency,
+            _ => {
+                return Err(format!(
+                    "Invalid frequency: {frequency}. Must be a positive number"
+                ))
+            }
+        }
+    } else if let Some(Ok(degree)) = pitch.strip_prefix('s').map(str::parse::<i32>) {
+        tuning.degree_frequency(degree)?
+    } else {
+        let pitch = pitch.parse::<Pitch>()?;
+        let frequency = pitch.frequency(tuning)?;
+        return Ok((pitch, frequency));
+    };
+    let pitch = Pitch {
+        name: pitch.to_string(),
+        ..Pitch::from_frequency(frequency)
+    };
+    Ok((pitch, frequency))
+}
+
+/// Parse the value part of a note, where several note values joined by `+` are tied into
+/// one, e.g. `2+8` is a half note tied to an eighth.
+fn parse_tied_value(s: &str) -> Result<NoteValue, ArgumentParseError> {
+    s.split('+').try_fold(NoteValue::zero(), |sum, value| {
+        sum.checked_add(parse_note_value(value)?).ok_or_else(|| {
+            ArgumentParseError::new(format!("Note value '{s}' is too long").as_str())
+        })
+    })
+}
+
+/// Parse the amplitude prefix `@<amplitude>` of a voice, e.g. `@0.5`.
+fn parse_voice_amplitude(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(amplitude) if (0.0..=1.0).contains(&amplitude) => Ok(amplitude),
+        _ => Err(format!(
+            "Invalid voice amplitude '@{s}'. Must be a number from 0 to 1, e.g. @0.5"
+        )),
+    }
+}
+
+/// Parse the stereo position of a `@pan:<position>` option, e.g. `@pan:-0.5`.
+fn parse_pan(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(pan) if (-1.0..=1.0).contains(&pan) => Ok(pan),
+        _ => Err(format!(
+            "Invalid pan '{s}'. Must be a number from -1 (left) to 1 (right), e.g. @pan:-0.5"
+        )),
+    }
+}
+
+/// Remove `//` comments from a sequence source, e.g. the contents of a sequence file. Line
+/// breaks are kept, and are treated like any other whitespace by the parser.
+pub fn strip_comments(source: &str) -> String {
+    source
+        .lines()
+        .map(|line| match line.find("//") {
+            Some(start) => &line[..start],
+            None => line,
+        })
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+/// Parse an option at the start of a voice, without its leading `@`.
+fn parse_voice_option(voice: &mut Voice, option: &str) -> Result<(), String> {
+    if let Some(position) = option.strip_prefix("pan:") {
+        voice.pan = parse_pan(position)?;
+    } else if option.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        voice.amplitude = parse_voice_amplitude(option)?;
+    } else {
+        voice.waveform = Some(option.parse::<Waveform>()?);
+    }
+    Ok(())
+}
+
+/// The pitches of a note, as written in the source.
+enum Pitches<'a> {
+    Single(&'a str, Span),
+    /// Pitches of a chord, with the index of the token of each pitch.
+    Chord(Vec<(&'a str, Span, usize)>),
+}
+
+/// Options and notes of the voice being parsed.
+struct VoiceState {
+    voice: Voice,
+    /// Scale and token index of every open tuplet group.
+    tuplets: Vec<(NoteValue, usize)>,
+    /// Position of the end of the last note, as a fraction of a whole note.
+    position: NoteValue,
+    /// Token index of a tie waiting for the note it ties to.
+    tie: Option<usize>,
+    /// Index in the voice, span and token index of every note that is shorter than one
+    /// sample, unless a note is tied to it later.
+    empty_notes: Vec<(usize, Span, usize)>,
+}
+
+/// Parser turning the tokens of a sequence into voices of notes. Errors are collected rather
+/// than returned, so that every error of the sequence can be reported at once.
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+    tuning: &'a Tuning,
+    tempo: u32,
+    sample_rate: u32,
+    errors: Vec<ArgumentParseError>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str, tuning: &'a Tuning, tempo: u32, sample_rate: u32) -> Self {
+        Parser {
+            source,
+            tokens: tokenize(source),
+            pos: 0,
+            tuning,
+            tempo,
+            sample_rate,
+            errors: Vec::new(),
+        }
+    }
+
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<(usize, Token<'a>)> {
+        let token = self.peek()?;
+        self.pos += 1;
+        Some((self.pos, token))
+    }
+
+    fn error(&mut self, msg: &str, span: Span, token: usize) {
+        self.errors
+            .push(ArgumentParseError::new(msg).at(span, token));
+    }
+
+    fn token_span(&self, token: usize) -> Span {
+        self.tokens[token - 1].span
+    }
+
+    fn parse_sequence(&mut self) -> Vec<Voice> {
+        let mut voices = Vec::new();
+        loop {
+            let voice = self.parse_voice();
+            if !voice.notes.is_empty() {
+                voices.push(voice);
+            }
+            if self.next().is_none() {
+                return voices;
+            }
+        }
+    }
+
+    /// Parse the options and notes of a voice, up to the next `|` or the end of the sequence.
+    fn parse_voice(&mut self) -> Voice {
+        let mut state = VoiceState {
+            voice: Voice::new(Vec::new()),
+            tuplets: Vec::new(),
+            position: NoteValue::zero(),
+            tie: None,
+            empty_notes: Vec::new(),
+        };
+
+        while let Some(token) = self.peek() {
+            if token.kind != TokenKind::Word || !token.text.starts_with('@') {
+                break;
+            }
+            let (index, token) = self.next().unwrap();
+            if let Err(e) = parse_voice_option(&mut state.voice, &token.text[1..]) {
+                self.error(&e, token.span, index);
+            }
+        }
+
+        while let Some(token) = self.peek() {
+            if token.kind == TokenKind::VoiceSeparator {
+                break;
+            }
+            let (index, token) = self.next().unwrap();
+            match token.kind {
+                TokenKind::Word if token.text.starts_with('@') => self.error(
+                    "Voice options must come before the notes of the voice",
+                    token.span,
+                    index,
+                ),
+                TokenKind::Word => {
+                    let pitch_len = token.text.find([':', '@']).unwrap_or(token.text.len());
+                    let pitches =
+                        Pitches::Single(&token.text[..pitch_len], token.span.sub(0, pitch_len));
+                    let suffix = token.span.sub(pitch_len, token.text.len() - pitch_len);
+                    self.parse_note(&mut state, pitches, suffix, token.span, index);
+                }
+                TokenKind::ChordOpen => self.parse_chord(&mut state, token, index),
+                TokenKind::ChordClose => self.error("Unmatched ']'", token.span, index),
+                TokenKind::TupletOpen => self.parse_tuplet_open(&mut state, token, index),
+                TokenKind::TupletClose => {
+                    if state.tuplets.pop().is_none() {
+                        self.error("Unmatched '}'", token.span, index);
+                    }
+                }
+                TokenKind::Tie => {
+                    if state.voice.notes.is_empty() && state.tie.is_none() {
+                        self.error("Tie has no preceding note", token.span, index);
+                    } else {
+                        state.tie = Some(index);
+                    }
+                }
+                TokenKind::VoiceSeparator => unreachable!(),
+            }
+        }
+
+        for &(_, index) in &state.tuplets {
+            self.error(
+                "Tuplet group was not closed with '}'",
+                self.token_span(index),
+                index,
+            );
+        }
+        if let Some(index) = state.tie {
+            self.error("Tie has no following note", self.token_span(index), index);
+        }
+
+        // A note of zero samples would otherwise be held indefinitely by the player
+        for &(note, span, index) in &state.empty_notes {
+            if state.voice.notes[note].num_samples == 0 {
+                let msg = format!(
+                    "Note is shorter than one sample at a sample rate of {} Hz",
+                    self.sample_rate
+                );
+                self.error(&msg, span, index);
+            }
+        }
+        state.voice
+    }
+
+    /// Parse the ratio following a `{`, which opens a tuplet group.
+    fn parse_tuplet_open(&mut self, state: &mut VoiceState, open: Token, index: usize) {
+        let scale = match self.peek() {
+            Some(token) if token.kind == TokenKind::Word => {
+                let (ratio_index, ratio) = self.next().unwrap();
+                parse_tuplet_ratio(ratio.text).map_err(|e| e.at(ratio.span, ratio_index))
+            }
+            _ => Err(ArgumentParseError::new(
+                "Tuplet group must start with a ratio n:m, e.g. {3:2",
+            )
+            .at(open.span, index)),
+        };
+        match scale {
+            Ok(scale) => state.tuplets.push((scale, index)),
+            Err(e) => {
+                // Keep the group open so that its closing brace is not reported as well
+                state.tuplets.push((NoteValue::whole(), index));
+                self.errors.push(e);
+            }
+        }
+    }
+
+    /// Parse the pitches following a `[` up to the closing `]`, and the note value and
+    /// options immediately following it, e.g. `[C4 E4 G4]:2@saw`.
+    fn parse_chord(&mut self, state: &mut VoiceState, open: Token, index: usize) {
+        let mut pitches = Vec::new();
+        let close = loop {
+            match self.peek() {
+                Some(token) if token.kind == TokenKind::Word => {
+                    let (pitch_index, _) = self.next().unwrap();
+                    pitches.push((token.text, token.span, pitch_index));
+                }
+                Some(token) if token.kind == TokenKind::ChordClose => {
+                    self.next();
+                    break token;
+                }
+                _ => {
+                    self.error("Chord was not closed with ']'", open.span, index);
+                    return;
+                }
+            }
+        };
+
+        let suffix = match self.peek() {
+            Some(token)
+                if token.kind == TokenKind::Word
+                    && token.span.start == close.span.end
+                    && token.text.starts_with([':', '@']) =>
+            {
+                self.next();
+                token.span
+            }
+            _ => Span::new(close.span.end, close.span.end),
+        };
+        let span = Span::new(open.span.start, suffix.end);
+        if pitches.is_empty() {
+            self.error("Chord contains no pitches", span, index);
+            return;
+        }
+        self.parse_note(state, Pitches::Chord(pitches), suffix, span, index);
+    }
+
+    /// Parse the note value and options in `suffix` of a note with `pitches`, and add the note
+    /// to the voice.
+    fn parse_note(
+        &mut self,
+        state: &mut VoiceState,
+        pitches: Pitches,
+        suffix: Span,
+        span: Span,
+        index: usize,
+    ) {
+        let tie = state.tie.take();
+        match self.build_note(state, pitches, suffix, span, index) {
+            Ok(note) => {
+                if tie.is_some() {
+                    match state.voice.notes.last_mut() {
+                        Some(last) if last.frequencies == note.frequencies => {
+                            last.num_samples += note.num_samples;
+                            last.value = last
+                                .value
+                                .zip(note.value)
+                                .and_then(|(value, tied)| value.checked_add(tied));
+                        }
+                        _ => self.error(
+                            "Cannot tie to the preceding note, since they differ in pitch",
+                            span,
+                            index,
+                        ),
+                    }
+                } else {
+                    if note.num_samples == 0 {
+                        state
+                            .empty_notes
+                            .push((state.voice.notes.len(), span, index));
+                    }
+                    state.voice.notes.push(note);
+                }
+            }
+            Err(e) => self.errors.push(e),
+        }
+    }
+
+    fn build_note(
+        &mut self,
+        state: &mut VoiceState,
+        pitches: Pitches,
+        suffix: Span,
+        span: Span,
+        index: usize,
+    ) -> Result<Note, ArgumentParseError> {
+        let source = self.source;
+        let text = &source[suffix.start..suffix.end];
+        let value_len = text.find('@').unwrap_or(text.len());
+
+        let value = match text[..value_len].strip_prefix(':') {
+            // Notes without a note value last for one second
+            None => NoteValue::new(self.tempo as u64, 240),
+            Some(value) if value.contains(':') => {
+                return Err(ArgumentParseError::new(
+                    "Invalid note. Must be on the format <pitch>:<note value>",
+                )
+                .at(span, index))
+            }
+            Some(value) => {
+                parse_tied_value(value).map_err(|e| e.at(suffix.sub(1, value.len()), index))?
+            }
+        };
+
+        let (mut waveform, mut pan) = (None, None);
+        let mut offset = value_len;
+        for option in text[value_len..].split('@').skip(1) {
+            let option_span = suffix.sub(offset, option.len() + 1);
+            offset += option.len() + 1;
+            let result = match option.strip_prefix("pan:") {
+                Some(position) => parse_pan(position).map(|position| pan = Some(position)),
+                None => option.parse().map(|option| waveform = Some(option)),
+            };
+            result.map_err(|e| ArgumentParseError::new(&e).at(option_span, index))?;
+        }
+
+        let too_long =
+            || ArgumentParseError::new("Note is too long or too finely divided").at(span, index);
+        let value = state
+            .tuplets
+            .iter()
+            .try_fold(value, |value, &(scale, _)| value.checked_mul(scale))
+            .ok_or_else(too_long)?;
+
+        // Round the absolute positions rather than each note value, so that rounding errors
+        // do not accumulate over the sequence.
+        let start = state.position.to_samples(self.tempo, self.sample_rate);
+        state.position = state.position.checked_add(value).ok_or_else(too_long)?;
+        let num_samples = state.position.to_samples(self.tempo, self.sample_rate) - start;
+
+        let (pitches, error_span, error_index) = match pitches {
+            Pitches::Single(pitch, _) if is_rest(pitch) => (Vec::new(), span, index),
+            Pitches::Single(pitch, pitch_span) => {
+                (vec![(pitch, pitch_span, index)], pitch_span, index)
+            }
+            Pitches::Chord(pitches) => (pitches, span, index),
+        };
+        let (pitches, frequencies): (Vec<Pitch>, Vec<f32>) = pitches
+            .iter()
+            .map(|&(pitch, pitch_span, pitch_index)| {
+                parse_pitch(pitch, self.tuning)
+                    .map_err(|e| ArgumentParseError::new(&e).at(pitch_span, pitch_index))
+            })
+            .collect::<Result<Vec<(Pitch, f32)>, ArgumentParseError>>()?
+            .into_iter()
+            .unzip();
+        let note = match pitches.is_empty() {
+            true => Note::rest(num_samples),
+            false => Note::chord(
+                frequencies,
+                DEFAULT_AMPLITUDE,
+                self.sample_rate,
+                num_samples,
+            )
+            .map_err(|e| ArgumentParseError::new(&e).at(error_span, error_index))?,
+        };
+        Ok(Note {
+            pitches,
+            waveform,
+            pan,
+            value: Some(value),
+            ..note
+        })
+    }
+}
+
+/// Parse a sequence of voices separated by `|`.
+///
+/// Within a voice, notes are written as `<pitch>:<note value>` and separated by whitespace. Notes
+/// can be grouped into tuplets with `{n:m ... }`, which plays the n notes of the group in the time
+/// of m, e.g. `{3:2 C:8 D:8 E:8}` plays three eighths in the time of two. Groups can be nested.
+/// Notes of the same pitch joined by `~` are tied into a single note, e.g. `C4:2~C4:8`, and the tie
+/// can span tokens, e.g. `C4:2~ C4:8`. Pitches enclosed in brackets are played together as a chord,
+/// e.g. `[C4 E4 G4]:2`. Pitches can be raised and lowered by quarter tones with `+` and `d` or by
+/// cents, e.g. `A4+15c`, and a pitch can be given as a frequency, e.g. `440hz:4`, or as a MIDI note
+/// number, e.g. `m60:4`. A note can set its own waveform with an `@<waveform>` suffix and its
+/// stereo position with an `@pan:<position>` suffix, e.g. `C4:4@square@pan:0.5`.
+///
+/// A voice can start with `@<amplitude>` to set the gain of the voice, `@<waveform>` to set
+/// the waveform of its notes and `@pan:<position>` to set its stereo position, e.g.
+/// `@0.5 @square @pan:-1 C4:4 E4:4 | @0.3 C3:2`. Parts without any notes are ignored.
+///
+/// The arguments in `s` are joined by spaces before parsing. Every error found in the
+/// sequence is returned, each pointing to its location in the joined source.
+pub fn parse_voices(
+    s: &[String],
+    tuning: &Tuning,
+    tempo: u32,
+    sample_rate: u32,
+) -> Result<Vec<Voice>, ParseErrors> {
+    let source = s.join(" ");
+    if tempo == 0 {
+        return Err(ParseErrors::new(
+            vec![ArgumentParseError::new("Tempo must be greater than zero")],
+            &source,
+        ));
+    }
+
+    let mut parser = Parser::new(&source, tuning, tempo, sample_rate);
+    let voices = parser.parse_sequence();
+    if parser.errors.is_empty() {
+        Ok(voices)
+    } else {
+        Err(ParseErrors::new(parser.errors, &source))
+    }
+}
+
+/// Parse a sequence of a single voice, see [`parse_voices`] for the syntax.
+pub fn parse_notes(
+    s: &[String],
+    tuning: &Tuning,
+    tempo: u32,
+    sample_rate: u32,
+) -> Result<Vec<Note>, ParseErrors> {
+    let mut voices = parse_voices(s, tuning, tempo, sample_rate)?;
+    match voices.len() {
+        0 | 1 => Ok(voices.pop().map(|voice| voice.notes).unwrap_or_default()),
+        _ => Err(ParseErrors::new(
+            vec![ArgumentParseError::new(
+                "Expected a single voice, use parse_voices to parse several voices",
+            )],
+            &s.join(" "),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEMPO: u32 = 120;
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn parse(source: &str) -> Result<Vec<Voice>, ParseErrors> {
+        parse_voices(
+            &[source.to_string()],
+            &Tuning::new(440.0),
+            TEMPO,
+            SAMPLE_RATE,
+        )
+    }
+
+    fn parse_error(source: &str) -> ParseErrors {
+        parse(source).expect_err(&format!("'{source}' should fail to parse"))
+    }
+
+    #[test]
+    fn note_values() {
+        let value = |s| parse_note_value(s).unwrap();
+        assert_eq!(value("1"), NoteValue::whole());
+        assert_eq!(value("4"), NoteValue::new(1, 4));
+        assert_eq!(value("4."), NoteValue::new(3, 8));
+        assert_eq!(value("8.."), NoteValue::new(7, 32));
+        assert_eq!(value("8t"), NoteValue::new(1, 12));
+        assert_eq!(value("4.t"), NoteValue::new(1, 4));
+    }
+
+    #[test]
+    fn invalid_note_values() {
+        let too_many_dots = format!("4{}", ".".repeat(17));
+        for s in [
+            "",
+            ".",
+            "t",
+            "0",
+            "3",
+            "12",
+            "4.4",
+            ".4",
+            "x",
+            "-4",
+            too_many_dots.as_str(),
+        ] {
+            assert!(parse_note_value(s).is_err(), "'{s}' should be invalid");
+        }
+    }
+
+    #[test]
+    fn notes() {
+        let voices = parse("C4:4 r:8. A4:2+8").unwrap();
+        assert_eq!(voices.len(), 1);
+        let notes = &voices[0].notes;
+        assert_eq!(notes.len(), 3);
+        assert_eq!(notes[0].pitches[0].name, "C4");
+        assert_eq!(notes[0].num_samples, 24000);
+        assert!(notes[1].frequencies.is_empty());
+        assert_eq!(notes[1].num_samples, 18000);
+        assert_eq!(notes[2].frequencies, vec![440.0]);
+        assert_eq!(notes[2].value, Some(NoteValue::new(5, 8)));
+        assert_eq!(notes[2].num_samples, 60000);
+    }
+
+    #[test]
+    fn voices() {
+        let voices = parse("@0.5 @square @pan:-1 C4:4 E4:4 | | @0.3 C3:2").unwrap();
+        assert_eq!(voices.len(), 2);
+        assert_eq!(voices[0].amplitude, 0.5);
+        assert_eq!(voices[0].waveform, Some(Waveform::Square));
+        assert_eq!(voices[0].pan, -1.0);
+        assert_eq!(voices[0].notes.len(), 2);
+        assert_eq!(voices[1].amplitude, 0.3);
+        assert_eq!(voices[1].notes.len(), 1);
+    }
+
+    #[test]
+    fn chords() {
+        let voices = parse("[C4 E4 G4]:2@saw [A4]:4").unwrap();
+        let notes = &voices[0].notes;
+        let names: Vec<&str> = notes[0].pitches.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["C4", "E4", "G4"]);
+        assert_eq!(notes[0].frequencies.len(), 3);
+        assert_eq!(notes[0].waveform, Some(Waveform::Sawtooth));
+        assert_eq!(notes[0].num_samples, 48000);
+        assert_eq!(notes[1].frequencies, vec![440.0]);
+    }
+
+    #[test]
+    fn ties() {
+        let voices = parse("C4:2~C4:8 ~ C4:16 [C4 E4]:4~[C4 E4]:4").unwrap();
+        let notes = &voices[0].notes;
+        assert_eq!(notes.len(), 2);
+        assert_eq!(notes[0].value, Some(NoteValue::new(11, 16)));
+        assert_eq!(notes[0].num_samples, 66000);
+        assert_eq!(notes[1].value, Some(NoteValue::new(1, 2)));
+        assert_eq!(notes[1].num_samples, 48000);
+    }
+
+    #[test]
+    fn tuplets() {
+        let voices =
+            parse("{3:2 C4:8 D4:8 E4:8} {3:2 {5:4 C4:16 C4:16 C4:16 C4:16 C4:16} C4:8}").unwrap();
+        let notes = &voices[0].notes;
+        assert_eq!(notes.len(), 9);
+        assert_eq!(notes[0].value, Some(NoteValue::new(1, 12)));
+        assert_eq!(notes[3].value, Some(NoteValue::new(1, 30)));
+        assert_eq!(notes[8].value, Some(NoteValue::new(1, 12)));
+        let total: u128 = notes.iter().map(|note| note.num_samples).sum();
+        assert_eq!(total, 48000);
+    }
+
+    #[test]
+    fn no_rounding_drift() {
+        // At 97 bpm and 44.1 kHz neither a septuplet nor a quintuplet sixteenth is a whole
+        // number of samples, so rounding each note would drift from the exact total
+        let group =
+            "{7:4 C4:16 C4:16 C4:16 C4:16 C4:16 C4:16 C4:16} {5:4 r:16 r:16 r:16 r:16 r:16}";
+        let source = vec![group; 100].join(" ");
+        let voices = parse_voices(&[source], &Tuning::new(440.0), 97, 44100).unwrap();
+        let total: u128 = voices[0].notes.iter().map(|note| note.num_samples).sum();
+        assert_eq!(total, NoteValue::new(50, 1).to_samples(97, 44100));
+        assert!(voices[0].notes[..7]
+            .iter()
+            .all(|note| note.value == Some(NoteValue::new(1, 28))));
+    }
+
+    #[test]
+    fn error_spans() {
+        let errors = parse_error("C4:4 X4:4 D4:3");
+        let errors = errors.errors();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].span(), Some(Span::new(5, 7)));
+        assert_eq!(errors[0].token(), Some(2));
+        assert_eq!(errors[1].span(), Some(Span::new(13, 14)));
+        assert_eq!(errors[1].token(), Some(3));
+
+        let errors = parse_error("[C4 Q4]:4");
+        assert_eq!(errors.errors()[0].span(), Some(Span::new(4, 6)));
+        assert_eq!(errors.errors()[0].token(), Some(3));
+
+        let errors = parse_error("C4:4 {3:2 C4:8");
+        assert_eq!(errors.errors()[0].span(), Some(Span::new(5, 6)));
+    }
+
+    #[test]
+    fn error_context() {
+        let errors = parse_error("C4:4 D4:3");
+        assert_eq!(
+            errors.to_string(),
+            "Note duration 3 was not a power of two\n \
+            --> token 2, line 1, column 9\n  \
+            |\n\
+            1 | C4:4 D4:3\n  \
+            |         ^"
+        );
+
+        let errors = parse_voices(
+            &[strip_comments("C4:4 // intro\n\tE4:4 Hb4:4")],
+            &Tuning::new(440.0),
+            TEMPO,
+            SAMPLE_RATE,
+        )
+        .unwrap_err();
+        assert_eq!(
+            errors.errors()[0]
+                .to_string()
+                .lines()
+                .skip(1)
+                .collect::<Vec<_>>(),
+            [
+                " --> token 3, line 2, column 7",
+                "  |",
+                "2 |  E4:4 Hb4:4",
+                "  |       ^^^"
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_sequences() {
+        for source in [
+            ":4",
+            "C4:",
+            "C4:4:4",
+            "C4::4",
+            "C4:4@",
+            "C4:4@pan:",
+            "C4:4@pan:2",
+            "C4:4@foo",
+            "C4:99999999999",
+            "C4:2147483648",
+            "C4:1+1+1+1t.",
+            "r:2147483648",
+            "[",
+            "]",
+            "[]:4",
+            "[C4 E4",
+            "[C4 {]",
+            "{",
+            "}",
+            "{3:2",
+            "{0:2 C4:4}",
+            "{3:0 C4:4}",
+            "{3 C4:4}",
+            "~",
+            "~ C4:4",
+            "C4:4 ~",
+            "C4:4~D4:4",
+            "C4:4~[C4 E4]:4",
+            "@",
+            "@2",
+            "@pan:",
+            "C4:4 @0.5",
+            "m128",
+            "m-1",
+            "s3",
+            "0hz",
+            "-1hz",
+            "nanhz",
+            "infhz",
+            "30000hz",
+            "C11",
+            "C-2",
+            "A4+9999999999999999999999999999999999999999c",
+            "Cb-1",
+            "é:4",
+            "C4:4 é",
+            "C4:4\u{301}",
+        ] {
+            let errors = parse_error(source);
+            assert!(!errors.errors().is_empty());
+            let rendered = errors.to_string();
+            assert!(
+                !rendered.is_empty(),
+                "'{source}' has an empty error message"
+            );
+        }
+    }
+
+    #[test]
+    fn valid_edge_cases() {
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse("| @0.5 |").unwrap().is_empty());
+        assert_eq!(parse("C4:2~ C4:2147483648").unwrap()[0].notes.len(), 1);
+        assert_eq!(parse("A4").unwrap()[0].notes[0].num_samples, 48000);
+        assert!(parse_voices(&[], &Tuning::new(440.0), TEMPO, SAMPLE_RATE)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn zero_tempo() {
+        let errors =
+            parse_voices(&["C4:4".to_string()], &Tuning::new(440.0), 0, SAMPLE_RATE).unwrap_err();
+        assert_eq!(errors.to_string(), "Tempo must be greater than zero");
+    }
+
+    #[test]
+    fn single_voice() {
+        let tuning = Tuning::new(440.0);
+        let notes = parse_notes(&["C4:4".to_string()], &tuning, TEMPO, SAMPLE_RATE).unwrap();
+        assert_eq!(notes.len(), 1);
+        assert!(parse_notes(&["C4:4 | D4:4".to_string()], &tuning, TEMPO, SAMPLE_RATE).is_err());
+    }
+}
@@ -0,0 +1,369 @@
+use crate::envelope::Envelope;
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use crate::mixer::{pan, Mixer};
+use crate::note::Note;
+use crate::sequence::{Sequence, Voice};
+use crate::waveform::{Noise, Oscillator, Waveform};
+
+/// A note which has ended but is still fading out.
+struct Release {
+    note: Note,
+    oscillators: Vec<Oscillator>,
+    /// Gain of the envelope when the note was released.
+    level: f32,
+    sample_num: u64,
+}
+
+/// Playback position within a single voice.
+struct VoicePlayer {
+    pos: std::vec::IntoIter<Note>,
+    amplitude: f32,
+    waveform: Option<Waveform>,
+    pan: f32,
+    noise: Noise,
+    sample_num: u64,
+    current_note: Option<Note>,
+    /// One oscillator per pitch of the current note. The oscillators carry their phase over
+    /// to the next note, so a change of pitch does not cause a discontinuity.
+    oscillators: Vec<Oscillator>,
+    releases: Vec<Release>,
+}
+
+impl VoicePlayer {
+    fn new(voice: Voice, seed: u32) -> Self {
+        let mut pos = voice.notes.into_iter();
+        let current_note = pos.next();
+        let oscillators = vec![Oscillator::new(); current_note.as_ref().map_or(0, polyphony)];
+
+        VoicePlayer {
+            pos,
+            amplitude: voice.amplitude,
+            waveform: voice.waveform,
+            pan: voice.pan,
+            noise: Noise::new(seed),
+            sample_num: 0,
+            current_note,
+            oscillators,
+            releases: Vec::new(),
+        }
+    }
+
+    fn next_note(&mut self) -> Option<Note> {
+        self.pos.next()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.current_note.is_none() && self.releases.is_empty()
+    }
+
+    /// Get the sum of every pitch sounding in the voice as a frame of left and right samples,
+    /// i.e. the current note and the notes still being released. `waveform` is used for notes
+    /// and voices that do not set their own.
+    fn frame(&mut self, sample_rate: u32, waveform: Waveform, envelope: &Envelope) -> (f32, f32) {
+        let waveform = self.waveform.unwrap_or(waveform);
+        let (mut left, mut right) = (0.0, 0.0);
+        if let Some(n) = &self.current_note {
+            let gain = envelope.held_gain(self.sample_num as f32 / sample_rate as f32);
+            let sample = note_sample(
+                n,
+                &mut self.oscillators,
+                sample_rate,
+                waveform,
+                &mut self.noise,
+            ) * gain;
+            let (l, r) = pan(sample * self.amplitude, n.pan.unwrap_or(self.pan));
+            (left, right) = (left + l, right + r);
+        }
+        for release in &mut self.releases {
+            let elapsed = release.sample_num as f32 / sample_rate as f32;
+            let gain = envelope
+                .released_gain(release.level, elapsed)
+                .unwrap_or(0.0);
+            let sample = note_sample(
+                &release.note,
+                &mut release.oscillators,
+                sample_rate,
+                waveform,
+                &mut self.noise,
+            ) * gain;
+            let (l, r) = pan(
+                sample * self.amplitude,
+                release.note.pan.unwrap_or(self.pan),
+            );
+            (left, right) = (left + l, right + r);
+        }
+        (left, right)
+    }
+
+    /// Move one sample forward in the current note, moving on to the next note once the
+    /// current one has finished and releasing the finished one.
+    fn advance(&mut self, sample_rate: u32, envelope: &Envelope) {
+        for release in &mut self.releases {
+            release.sample_num += 1;
+        }
+        self.releases.retain(|release| {
+            let elapsed = release.sample_num as f32 / sample_rate as f32;
+            envelope.released_gain(release.level, elapsed).is_some()
+        });
+
+        let Some(current_note) = &self.current_note else {
+            return;
+        };
+        self.sample_num += 1;
+        if current_note.num_samples == 0 || (self.sample_num as u128) < current_note.num_samples {
+            return;
+        }
+
+        let level = envelope.held_gain(self.sample_num as f32 / sample_rate as f32);
+        let next_note = self.next_note();
+        let next_polyphony = next_note.as_ref().map_or(0, polyphony);
+        if let Some(note) = std::mem::replace(&mut self.current_note, next_note) {
+            if !note.is_rest() && !envelope.release.is_zero() {
+                self.releases.push(Release {
+                    note,
+                    oscillators: self.oscillators.clone(),
+                    level,
+                    sample_num: 0,
+                });
+            }
+        }
+        self.oscillators.resize(next_polyphony, Oscillator::new());
+        self.sample_num = 0;
+    }
+}
+
+fn polyphony(note: &Note) -> usize {
+    note.frequencies.len()
+}
+
+/// Get the sum of the pitches of `n`, scaled by the amplitude of the note, and move every
+/// oscillator one sample forward.
+fn note_sample(
+    n: &Note,
+    oscillators: &mut [Oscillator],
+    sample_rate: u32,
+    waveform: Waveform,
+    noise: &mut Noise,
+) -> f32 {
+    let waveform = n.waveform.unwrap_or(waveform);
+    n.frequencies
+        .iter()
+        .zip(oscillators.iter_mut())
+        .map(|(&frequency, oscillator)| {
+            oscillator.next_sample(waveform, frequency, sample_rate, noise)
+        })
+        .sum::<f32>()
+        * n.amplitude
+}
+
+/// Renders a [`Sequence`] into stereo frames, one frame per call to
+/// [`Player::get_next_frame`], or mono samples with [`Player::get_next_sample`]. Every voice
+/// advances independently, and the panned pitches of all voices are summed by a [`Mixer`].
+///
+/// All state is owned by the player, so rendering the same sequence twice produces the same
+/// samples.
+pub struct Player {
+    voices: Vec<VoicePlayer>,
+    sample_rate: u32,
+    mixer: Mixer,
+    waveform: Waveform,
+    envelope: Envelope,
+}
+
+impl Player {
+    pub fn new(sequence: Sequence) -> Self {
+        let sample_rate = sequence.sample_rate();
+        let mixer = Mixer::new(sequence.polyphony());
+        let voices = sequence
+            .voices()
+            .iter()
+            .cloned()
+            .zip(1..)
+            .map(|(voice, seed)| VoicePlayer::new(voice, seed))
+            .collect();
+
+        Player {
+            voices,
+            sample_rate,
+            mixer,
+            waveform: Waveform::Sine,
+            envelope: Envelope::default(),
+        }
+    }
+
+    /// Set the waveform of notes and voices that do not set their own. Defaults to
+    /// [`Waveform::Sine`].
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        self.waveform = waveform;
+    }
+
+    /// Set the envelope applied to every note. Defaults to [`Envelope::default`].
+    pub fn set_envelope(&mut self, envelope: Envelope) {
+        self.envelope = envelope;
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Sum the next frames of every voice before they are mixed, or `None` once every voice
+    /// has finished.
+    fn next_frame_sum(&mut self) -> Option<(f32, f32)> {
+        if self.voices.iter().all(VoicePlayer::is_finished) {
+            return None;
+        }
+
+        let (sample_rate, waveform, envelope) = (self.sample_rate, self.waveform, self.envelope);
+        let frame = self
+            .voices
+            .iter_mut()
+            .map(|voice| voice.frame(sample_rate, waveform, &envelope))
+            .fold((0.0, 0.0), |(left, right), (l, r)| (left + l, right + r));
+
+        for voice in &mut self.voices {
+            voice.advance(sample_rate, &envelope);
+        }
+        Some(frame)
+    }
+
+    /// Get the next frame of the sequence as a pair of left and right samples, or `None`
+    /// once every voice has finished.
+    pub fn get_next_frame(&mut self) -> Option<(f32, f32)> {
+        let frame = self.next_frame_sum()?;
+        Some(self.mixer.mix_stereo([frame]))
+    }
+
+    /// Get the next sample of the sequence mixed down to mono, or `None` once every voice has
+    /// finished.
+    pub fn get_next_sample(&mut self) -> Option<f32> {
+        // Undo the constant power pan law, so that centered notes keep their amplitude. The
+        // channels are folded before the mixer clips, which keeps the mono mix within [-1, 1].
+        let (left, right) = self.next_frame_sum()?;
+        Some(self.mixer.mix([(left + right) * FRAC_1_SQRT_2]))
+    }
+
+    /// Fill one interleaved frame of `frame.len()` channels, returning `false` once every voice
+    /// has finished.
+    ///
+    /// A single channel gets the mono mix, and otherwise the first two channels get the left and
+    /// right samples while any further channels are left silent.
+    pub fn write_frame(&mut self, frame: &mut [f32]) -> bool {
+        frame.fill(0.0);
+        if frame.len() == 1 {
+            return match self.get_next_sample() {
+                Some(sample) => {
+                    frame[0] = sample;
+                    true
+                }
+                None => false,
+            };
+        }
+        match self.get_next_frame() {
+            Some((left, right)) => {
+                if let [l, r, ..] = frame {
+                    (*l, *r) = (left, right);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Iterator for Player {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        self.get_next_sample()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::note::DEFAULT_AMPLITUDE;
+    use crate::parser::parse_voices;
+    use crate::tuning::Tuning;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn sequence(source: &str) -> Sequence {
+        let voices =
+            parse_voices(&[source.to_string()], &Tuning::new(440.0), 120, SAMPLE_RATE).unwrap();
+        Sequence::with_voices(voices, SAMPLE_RATE)
+    }
+
+    fn render(source: &str, waveform: Waveform, envelope: Envelope) -> Vec<f32> {
+        let mut player = Player::new(sequence(source));
+        player.set_waveform(waveform);
+        player.set_envelope(envelope);
+        player.collect()
+    }
+
+    #[test]
+    fn deterministic() {
+        let source = "@noise C4:8 [E4 G4]:8 | @square @pan:0.5 A3:4 | r:16 B5:16@saw";
+        for waveform in [Waveform::Sine, Waveform::Noise] {
+            let first = render(source, waveform, Envelope::default());
+            assert_eq!(first, render(source, waveform, Envelope::default()));
+        }
+
+        let frames = |source| {
+            let mut player = Player::new(sequence(source));
+            std::iter::from_fn(|| player.get_next_frame()).collect::<Vec<_>>()
+        };
+        assert_eq!(frames(source), frames(source));
+    }
+
+    #[test]
+    fn continuous_phase() {
+        // Without an envelope, a sine changing pitch only stays smooth if its phase carries
+        // over, so no step can be larger than the steepest slope of the highest pitch
+        let envelope = Envelope::new(Duration::ZERO, Duration::ZERO, 1.0, Duration::ZERO);
+        let samples = render("A4:32 C5:64 A5:64 E4:32 A5:16", Waveform::Sine, envelope);
+        let max_step = DEFAULT_AMPLITUDE * 2.0 * PI * 880.0 / SAMPLE_RATE as f32;
+        for (i, pair) in samples.windows(2).enumerate() {
+            let step = (pair[1] - pair[0]).abs();
+            assert!(step <= max_step * 1.01, "Step of {step} at sample {i}");
+        }
+    }
+
+    #[test]
+    fn release_tail() {
+        // A quarter note at 120 bpm lasts 24000 samples, followed by a 10 ms release
+        assert_eq!(
+            render("A4:4", Waveform::Sine, Envelope::default()).len(),
+            24480
+        );
+        let no_release = Envelope {
+            release: Duration::ZERO,
+            ..Envelope::default()
+        };
+        assert_eq!(render("A4:4", Waveform::Sine, no_release).len(), 24000);
+        // The release of the last note fades out during the rest
+        assert_eq!(
+            render("A4:4 r:4", Waveform::Sine, Envelope::default()).len(),
+            48000
+        );
+        let samples = render("A4:4 | C4:8", Waveform::Sine, Envelope::default());
+        assert_eq!(samples.len(), 24480);
+        assert!(samples[24000..].iter().any(|&sample| sample != 0.0));
+    }
+
+    #[test]
+    fn stereo_frames() {
+        let mut player = Player::new(sequence("@pan:-1 A4:4"));
+        let frames: Vec<(f32, f32)> = std::iter::from_fn(|| player.get_next_frame()).collect();
+        assert!(frames.iter().all(|&(_, right)| right.abs() < 1e-6));
+        assert!(frames.iter().any(|&(left, _)| left.abs() > 0.5));
+
+        let mut player = Player::new(sequence("A4:4"));
+        let mut frame = [1.0; 4];
+        assert!(player.write_frame(&mut frame));
+        assert_eq!(frame[2..], [0.0, 0.0]);
+    }
+}
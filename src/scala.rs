@@ -0,0 +1,449 @@
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// Lines of a Scala file that are not comments, numbered from 1.
+fn get_lines(source: &str) -> impl Iterator<Item = (usize, &str)> {
+    source
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.starts_with('!'))
+}
+
+/// First word of a line, which holds the value of the line. Anything after it is ignored.
+fn get_value(line: &str) -> &str {
+    line.split_whitespace().next().unwrap_or_default()
+}
+
+/// Value of the next line, which holds the `name` field of a file.
+fn next_value<'a>(
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+    name: &str,
+) -> Result<(usize, &'a str), String> {
+    lines
+        .next()
+        .map(|(line_number, line)| (line_number, get_value(line)))
+        .ok_or_else(|| format!("Keyboard mapping is missing its {name}"))
+}
+
+/// Value of the next line as an integer within `range`.
+fn next_integer<'a>(
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+    name: &str,
+    range: RangeInclusive<i32>,
+) -> Result<i32, String> {
+    let (line_number, value) = next_value(lines, name)?;
+    match value.parse::<i32>() {
+        Ok(value) if range.contains(&value) => Ok(value),
+        _ => Err(format!("Line {line_number}: Invalid {name} '{value}'")),
+    }
+}
+
+/// A scale read from a Scala `.scl` file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scale {
+    pub description: String,
+    /// Pitches of the degrees above the first degree in cents, where the last degree is the
+    /// interval at which the scale repeats, e.g. 1200 for an octave.
+    pub cents: Vec<f64>,
+}
+
+impl Scale {
+    /// Parse a pitch of a scale, given either in cents if it contains a period, e.g. `701.955`,
+    /// or as a ratio otherwise, e.g. `3/2` or `2`.
+    fn parse_pitch(pitch: &str) -> Result<f64, String> {
+        let invalid = || format!("Invalid pitch '{pitch}'");
+        if pitch.contains('.') {
+            return pitch.parse::<f64>().map_err(|_| invalid());
+        }
+        let (numerator, denominator) = pitch.split_once('/').unwrap_or((pitch, "1"));
+        match (numerator.parse::<u64>(), denominator.parse::<u64>()) {
+            (Ok(numerator), Ok(denominator)) if numerator > 0 && denominator > 0 => {
+                Ok(1200.0 * (numerator as f64 / denominator as f64).log2())
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl FromStr for Scale {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut lines = get_lines(source);
+        let description = lines.next().map_or("", |(_, line)| line).to_string();
+        let (count_line, count) = lines
+            .next()
+            .ok_or_else(|| String::from("Scale is missing its number of notes"))?;
+        let count = get_value(count)
+            .parse::<usize>()
+            .map_err(|_| format!("Line {count_line}: Invalid number of notes '{count}'"))?;
+        if count == 0 {
+            return Err(String::from("Scale has no notes"));
+        }
+
+        let mut cents = Vec::with_capacity(count);
+        for (line_number, line) in lines.take(count) {
+            let pitch = Scale::parse_pitch(get_value(line))
+                .map_err(|e| format!("Line {line_number}: {e}"))?;
+            cents.push(pitch);
+        }
+        if cents.len() < count {
+            return Err(format!(
+                "Scale has {} notes, but {count} were declared",
+                cents.len()
+            ));
+        }
+        Ok(Scale { description, cents })
+    }
+}
+
+/// Mapping of MIDI keys to the degrees of a scale, read from a Scala `.kbm` file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyboardMapping {
+    /// Lowest and highest keys that are mapped.
+    pub first_key: i32,
+    pub last_key: i32,
+    /// Key that the first degree of the scale is mapped to.
+    pub middle_key: i32,
+    /// Key that is tuned to `reference_frequency`.
+    pub reference_key: i32,
+    pub reference_frequency: f64,
+    /// Degree that the mapping repeats at, or 0 for the last degree of the scale.
+    pub octave_degree: i32,
+    /// Degree of each key of the repeating pattern starting at `middle_key`, or `None` if the
+    /// key is not mapped. An empty pattern maps every key to a degree of its own.
+    pub map: Vec<Option<i32>>,
+}
+
+impl KeyboardMapping {
+    /// Mapping of consecutive keys to consecutive degrees, where middle C is the first
+    /// degree and A4 is tuned to `reference_frequency`.
+    pub fn linear(reference_frequency: f64) -> Self {
+        KeyboardMapping {
+            first_key: 0,
+            last_key: 127,
+            middle_key: 60,
+            reference_key: 69,
+            reference_frequency,
+            octave_degree: 0,
+            map: Vec::new(),
+        }
+    }
+}
+
+impl FromStr for KeyboardMapping {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut lines = get_lines(source).filter(|(_, line)| !line.is_empty());
+        let lines = &mut lines;
+        let size = next_integer(lines, "map size", 0..=128)?;
+        let first_key = next_integer(lines, "first key", 0..=127)?;
+        let last_key = next_integer(lines, "last key", 0..=127)?;
+        let middle_key = next_integer(lines, "middle key", 0..=127)?;
+        let reference_key = next_integer(lines, "reference key", 0..=127)?;
+        let (line_number, frequency) = next_value(lines, "reference frequency")?;
+        let reference_frequency = match frequency.parse::<f64>() {
+            Ok(frequency) if frequency > 0.0 => frequency,
+            _ => {
+                return Err(format!(
+                    "Line {line_number}: Invalid reference frequency '{frequency}'"
+                ))
+            }
+        };
+        let octave_degree = next_integer(lines, "octave degree", 0..=i32::MAX)?;
+
+        // Keys missing at the end of the pattern are not mapped
+        let mut map = vec![None; size as usize];
+        for entry in map.iter_mut() {
+            let Some((line_number, degree)) = lines.next() else {
+                break;
+            };
+            let degree = get_value(degree);
+            *entry =
+                match degree {
+                    "x" | "X" => None,
+                    degree => Some(degree.parse::<i32>().map_err(|_| {
+                        format!("Line {line_number}: Invalid scale degree '{degree}'")
+                    })?),
+                };
+        }
+        Ok(KeyboardMapping {
+            first_key,
+            last_key,
+            middle_key,
+            reference_key,
+            reference_frequency,
+            octave_degree,
+            map,
+        })
+    }
+}
+
+/// Tuning of a Scala scale, whose degrees are mapped to keys by a keyboard mapping.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scala {
+    pub scale: Scale,
+    pub mapping: KeyboardMapping,
+    /// Pitch in cents of the degree tuned to the reference frequency.
+    reference_cents: f64,
+}
+
+impl Scala {
+    pub fn new(scale: Scale, mapping: KeyboardMapping) -> Result<Self, String> {
+        let mut scala = Scala {
+            scale,
+            mapping,
+            reference_cents: 0.0,
+        };
+        let degree = scala
+            .get_degree(scala.mapping.reference_key)
+            .ok_or_else(|| {
+                format!(
+                    "Reference key {} of the keyboard mapping is not mapped to a scale degree",
+                    scala.mapping.reference_key
+                )
+            })?;
+        scala.reference_cents = scala.get_cents(degree);
+        Ok(scala)
+    }
+
+    /// Pitch in cents of `degree` above the first degree of the scale.
+    fn get_cents(&self, degree: i32) -> f64 {
+        let size = self.scale.cents.len() as i32;
+        let period = self.scale.cents[self.scale.cents.len() - 1];
+        let step = match degree.rem_euclid(size) {
+            0 => 0.0,
+            step => self.scale.cents[step as usize - 1],
+        };
+        period * degree.div_euclid(size) as f64 + step
+    }
+
+    /// Scale degree that `key` is mapped to, if any.
+    fn get_degree(&self, key: i32) -> Option<i32> {
+        let mapping = &self.mapping;
+        if key < mapping.first_key || key > mapping.last_key {
+            return None;
+        }
+        let distance = key - mapping.middle_key;
+        if mapping.map.is_empty() {
+            return Some(distance);
+        }
+        let size = mapping.map.len() as i32;
+        let octave_degree = match mapping.octave_degree {
+            0 => self.scale.cents.len() as i32,
+            degree => degree,
+        };
+        mapping.map[distance.rem_euclid(size) as usize]
+            .map(|degree| degree + octave_degree * distance.div_euclid(size))
+    }
+
+    /// Frequency of `degree` of the scale, counted from the degree of the middle key.
+    pub fn degree_frequency(&self, degree: i32) -> f32 {
+        let cents = self.get_cents(degree) - self.reference_cents;
+        (2f64.powf(cents / 1200.0) * self.mapping.reference_frequency) as f32
+    }
+
+    /// Frequency of the scale degree mapped to the MIDI key `key`.
+    pub fn key_frequency(&self, key: i32) -> Result<f32, String> {
+        self.get_degree(key)
+            .map(|degree| self.degree_frequency(degree))
+            .ok_or_else(|| format!("MIDI key {key} is not mapped to a scale degree"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Twelve-tone equal temperament.
+    const EQUAL: &str = "! equal.scl\n!\n12-TET\n 12\n!\n100.0\n200.\n300.0\n400.0\n500.0\n\
+        600.0\n700.0\n800.0\n900.0\n1000.0\n1100.0\n2/1\n";
+
+    /// Just major scale.
+    const MAJOR: &str = "Just major\n7\n9/8\n5/4\n4/3\n3/2 fifth\n5/3\n15/8\n2\n";
+
+    /// Mapping of the white keys to a seven note scale, with A4 at 440 Hz.
+    const WHITE_KEYS: &str = "! white.kbm\n12\n0\n127\n60\n69\n440.0\n7\n\
+        ! C\n0\nx\n1\nx\n2\n3\nx\n4\nx\n5\nx\n6\n";
+
+    fn scala(scale: &str, mapping: &str) -> Result<Scala, String> {
+        Scala::new(scale.parse().unwrap(), mapping.parse().unwrap())
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{actual} is not {expected}"
+        );
+    }
+
+    #[test]
+    fn scales() {
+        let scale: Scale = EQUAL.parse().unwrap();
+        assert_eq!(scale.description, "12-TET");
+        assert_eq!(scale.cents.len(), 12);
+        assert_close(scale.cents[0], 100.0);
+        assert_close(scale.cents[1], 200.0);
+        assert_close(scale.cents[11], 1200.0);
+
+        let scale: Scale = MAJOR.parse().unwrap();
+        assert_eq!(scale.description, "Just major");
+        let expected = [
+            203.910, 386.314, 498.045, 701.955, 884.359, 1088.269, 1200.0,
+        ];
+        for (cents, expected) in scale.cents.iter().zip(expected) {
+            assert_close(*cents, expected);
+        }
+
+        // The description may be empty, and the pitches may be negative
+        let scale: Scale = "\n2\n-50.0\n1200.0\n".parse().unwrap();
+        assert_eq!(scale.description, "");
+        assert_close(scale.cents[0], -50.0);
+    }
+
+    #[test]
+    fn invalid_scales() {
+        let sources = [
+            "",
+            "Scale",
+            "Scale\nmany\n2/1",
+            "Scale\n0\n",
+            "Scale\n2\n3/2\n",
+            "Scale\n1\n0/1\n",
+            "Scale\n1\n3/0\n",
+            "Scale\n1\n-3/2\n",
+            "Scale\n1\nfifth\n",
+            "Scale\n1\n1.2.3\n",
+        ];
+        for source in sources {
+            assert!(source.parse::<Scale>().is_err(), "{source:?} was parsed");
+        }
+        assert_eq!(
+            "Scale\n1\n3/x\n".parse::<Scale>(),
+            Err(String::from("Line 3: Invalid pitch '3/x'"))
+        );
+    }
+
+    #[test]
+    fn keyboard_mappings() {
+        let mapping: KeyboardMapping = WHITE_KEYS.parse().unwrap();
+        assert_eq!(
+            mapping,
+            KeyboardMapping {
+                first_key: 0,
+                last_key: 127,
+                middle_key: 60,
+                reference_key: 69,
+                reference_frequency: 440.0,
+                octave_degree: 7,
+                map: vec![
+                    Some(0),
+                    None,
+                    Some(1),
+                    None,
+                    Some(2),
+                    Some(3),
+                    None,
+                    Some(4),
+                    None,
+                    Some(5),
+                    None,
+                    Some(6),
+                ],
+            }
+        );
+
+        // Keys missing at the end of the pattern are not mapped
+        let mapping: KeyboardMapping = "3\n0\n127\n60\n69\n440\n0\n0\n1\n".parse().unwrap();
+        assert_eq!(mapping.map, [Some(0), Some(1), None]);
+
+        // A map size of 0 maps every key to a degree of its own
+        let mapping: KeyboardMapping = "0\n0\n127\n60\n69\n440.0\n0\n".parse().unwrap();
+        assert_eq!(mapping, KeyboardMapping::linear(440.0));
+    }
+
+    #[test]
+    fn invalid_keyboard_mappings() {
+        let sources = [
+            "",
+            "129\n0\n127\n60\n69\n440\n0\n",
+            "-1\n0\n127\n60\n69\n440\n0\n",
+            "0\n0\n128\n60\n69\n440\n0\n",
+            "0\n0\n127\n60\n69\n0\n0\n",
+            "0\n0\n127\n60\n69\nA4\n0\n",
+            "0\n0\n127\n60\n69\n440\n-1\n",
+            "0\n0\n127\n60\n69\n440\n",
+            "1\n0\n127\n60\n69\n440\n0\ny\n",
+        ];
+        for source in sources {
+            assert!(
+                source.parse::<KeyboardMapping>().is_err(),
+                "{source:?} was parsed"
+            );
+        }
+        assert_eq!(
+            "1\n0\n127\n60\n69\n440\n0\n! degree\ny\n".parse::<KeyboardMapping>(),
+            Err(String::from("Line 9: Invalid scale degree 'y'"))
+        );
+    }
+
+    #[test]
+    fn key_frequencies() {
+        let scala = scala(EQUAL, "0\n0\n127\n60\n69\n440.0\n0\n").unwrap();
+        assert_close(scala.key_frequency(69).unwrap() as f64, 440.0);
+        assert_close(scala.key_frequency(60).unwrap() as f64, 261.626);
+        assert_close(scala.key_frequency(81).unwrap() as f64, 880.0);
+        assert_close(scala.degree_frequency(9) as f64, 440.0);
+        assert_close(scala.degree_frequency(-3) as f64, 220.0);
+
+        let scala = self::scala(MAJOR, WHITE_KEYS).unwrap();
+        assert_close(scala.key_frequency(69).unwrap() as f64, 440.0);
+        assert_close(scala.key_frequency(64).unwrap() as f64, 330.0);
+        assert_close(scala.key_frequency(60).unwrap() as f64, 264.0);
+        assert_close(scala.key_frequency(72).unwrap() as f64, 528.0);
+        assert_close(scala.key_frequency(48).unwrap() as f64, 132.0);
+        assert_close(scala.degree_frequency(7) as f64, 528.0);
+        assert_eq!(
+            scala.key_frequency(61),
+            Err(String::from("MIDI key 61 is not mapped to a scale degree"))
+        );
+        assert!(scala.key_frequency(70).is_err());
+    }
+
+    #[test]
+    fn mapped_key_ranges() {
+        // Keys outside of the mapped range are not mapped
+        let scala = scala(EQUAL, "0\n48\n72\n60\n69\n440.0\n0\n").unwrap();
+        assert!(scala.key_frequency(48).is_ok());
+        assert!(scala.key_frequency(72).is_ok());
+        assert!(scala.key_frequency(47).is_err());
+        assert!(scala.key_frequency(73).is_err());
+
+        // The octave degree can differ from the size of the map, e.g. a seven note scale
+        // repeating every twelve keys where its last degree is the octave
+        let scala = self::scala(MAJOR, &WHITE_KEYS.replace("440.0\n7\n", "440.0\n0\n")).unwrap();
+        assert_close(scala.key_frequency(72).unwrap() as f64, 528.0);
+    }
+
+    #[test]
+    fn unmapped_reference_keys() {
+        let error = |mapping: &str| scala(EQUAL, mapping).unwrap_err();
+        // Outside of the mapped range
+        assert_eq!(
+            error("0\n0\n68\n60\n69\n440.0\n0\n"),
+            "Reference key 69 of the keyboard mapping is not mapped to a scale degree"
+        );
+        assert_eq!(
+            error("0\n70\n127\n72\n69\n440.0\n0\n"),
+            "Reference key 69 of the keyboard mapping is not mapped to a scale degree"
+        );
+        // Mapped to an x in the pattern
+        let mapping = WHITE_KEYS.replace("\n69\n", "\n70\n");
+        assert_eq!(
+            error(&mapping),
+            "Reference key 70 of the keyboard mapping is not mapped to a scale degree"
+        );
+    }
+}
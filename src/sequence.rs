@@ -0,0 +1,241 @@
+use crate::abc::parse_abc;
+use crate::midi::read_midi;
+use crate::musicxml::read_musicxml;
+use crate::note::{Note, Pitch};
+use crate::parser::{parse_voices, strip_comments, ParseErrors};
+use crate::tuning::Tuning;
+use crate::value::NoteValue;
+use crate::waveform::Waveform;
+
+/// An independent melodic line of a sequence.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Voice {
+    pub notes: Vec<Note>,
+    /// Gain applied to every note of the voice.
+    pub amplitude: f32,
+    /// Waveform of notes that do not set their own, or `None` to use the waveform of the
+    /// player.
+    pub waveform: Option<Waveform>,
+    /// Stereo position of notes that do not set their own, from -1 (left) to 1 (right).
+    pub pan: f32,
+}
+
+impl Voice {
+    pub fn new(notes: Vec<Note>) -> Self {
+        Voice::with_amplitude(notes, 1.0)
+    }
+
+    pub fn with_amplitude(notes: Vec<Note>, amplitude: f32) -> Self {
+        Voice {
+            notes,
+            amplitude,
+            waveform: None,
+            pan: 0.0,
+        }
+    }
+
+    /// Largest number of pitches the voice plays at once.
+    pub fn polyphony(&self) -> usize {
+        self.notes
+            .iter()
+            .map(|note| note.frequencies.len())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Note of an imported score, sounding from `start` to `end` in the time unit of the score.
+pub(crate) struct TimedNote<T> {
+    pub start: T,
+    pub end: T,
+    pub pitch: Pitch,
+    pub amplitude: f32,
+}
+
+/// Time line of an imported score, which converts its positions into samples and note values.
+pub(crate) trait Timeline<T> {
+    /// Position in samples, rounded to the nearest sample.
+    fn to_samples(&self, position: T, sample_rate: u32) -> u128;
+
+    /// Note value of the time from `start` to `end`, if the score measures time in beats.
+    fn to_note_value(&self, start: T, end: T) -> Option<NoteValue>;
+}
+
+/// Distribute the notes of an imported part over as few voices as possible, where notes that
+/// start and end together become a chord. Gaps between notes become rests, and every voice is
+/// padded with a rest up to `end`.
+pub(crate) fn arrange_voices<T: Copy + Ord + Default>(
+    mut notes: Vec<TimedNote<T>>,
+    end: T,
+    pan: f32,
+    timeline: &impl Timeline<T>,
+    tuning: &Tuning,
+    sample_rate: u32,
+) -> Result<Vec<Voice>, String> {
+    notes.sort_by_key(|note| (note.start, note.end, note.pitch.semitones));
+    let samples = |position| timeline.to_samples(position, sample_rate);
+    let push_rest = |voice: &mut Voice, start, end| {
+        let num_samples = samples(end) - samples(start);
+        if num_samples > 0 {
+            voice.notes.push(Note {
+                value: timeline.to_note_value(start, end),
+                ..Note::rest(num_samples)
+            });
+        }
+    };
+
+    // Every voice with the position at which its last note ends
+    let mut voices: Vec<(Voice, T)> = Vec::new();
+    for chord in notes.chunk_by(|a, b| (a.start, a.end) == (b.start, b.end)) {
+        let (start, end) = (chord[0].start, chord[0].end);
+        let amplitude = chord.iter().map(|note| note.amplitude).fold(0.0, f32::max);
+        let pitches: Vec<Pitch> = chord.iter().map(|note| note.pitch.clone()).collect();
+
+        let index = match voices.iter().position(|&(_, voice_end)| voice_end <= start) {
+            Some(index) => index,
+            None => {
+                let mut voice = Voice::new(Vec::new());
+                voice.pan = pan;
+                voices.push((voice, T::default()));
+                voices.len() - 1
+            }
+        };
+        let (voice, voice_end) = &mut voices[index];
+
+        push_rest(voice, *voice_end, start);
+        let num_samples = samples(end) - samples(start);
+        if num_samples > 0 {
+            let note = Note::chord(
+                pitches
+                    .iter()
+                    .map(|pitch| pitch.frequency(tuning))
+                    .collect::<Result<Vec<f32>, String>>()?,
+                amplitude,
+                sample_rate,
+                num_samples,
+            )?;
+            voice.notes.push(Note {
+                pitches,
+                value: timeline.to_note_value(start, end),
+                ..note
+            });
+        }
+        *voice_end = end;
+    }
+    Ok(voices
+        .into_iter()
+        .map(|(mut voice, voice_end)| {
+            push_rest(&mut voice, voice_end, end);
+            voice
+        })
+        .collect())
+}
+
+/// Voices played simultaneously at a fixed sample rate.
+#[derive(Clone, Debug)]
+pub struct Sequence {
+    voices: Vec<Voice>,
+    sample_rate: u32,
+}
+
+impl Sequence {
+    /// Create a sequence of a single voice.
+    pub fn new(notes: Vec<Note>, sample_rate: u32) -> Self {
+        Sequence::with_voices(vec![Voice::new(notes)], sample_rate)
+    }
+
+    pub fn with_voices(voices: Vec<Voice>, sample_rate: u32) -> Self {
+        Sequence {
+            voices,
+            sample_rate,
+        }
+    }
+
+    /// Parse a list of `<pitch>:<note value>` tokens into a sequence, where voices are
+    /// separated by `|`.
+    pub fn parse(
+        tokens: &[String],
+        tuning: &Tuning,
+        tempo: u32,
+        sample_rate: u32,
+    ) -> Result<Self, ParseErrors> {
+        Ok(Sequence::with_voices(
+            parse_voices(tokens, tuning, tempo, sample_rate)?,
+            sample_rate,
+        ))
+    }
+
+    /// Parse a sequence source, e.g. the contents of a sequence file, which can span several
+    /// lines and contain `//` comments.
+    pub fn from_source(
+        source: &str,
+        tuning: &Tuning,
+        tempo: u32,
+        sample_rate: u32,
+    ) -> Result<Self, ParseErrors> {
+        Sequence::parse(&[strip_comments(source)], tuning, tempo, sample_rate)
+    }
+
+    /// Parse the first tune of an ABC source into a sequence, see [`parse_abc`].
+    pub fn from_abc(
+        source: &str,
+        tuning: &Tuning,
+        tempo: u32,
+        sample_rate: u32,
+    ) -> Result<Self, ParseErrors> {
+        Ok(Sequence::new(
+            parse_abc(source, tuning, tempo, sample_rate)?,
+            sample_rate,
+        ))
+    }
+
+    /// Read the notes of a Standard MIDI File into a sequence, keeping only the `tracks`
+    /// (numbered from 0) and `channels` (numbered from 1) given, or all of them if empty.
+    pub fn from_midi(
+        data: &[u8],
+        tracks: &[usize],
+        channels: &[u8],
+        tuning: &Tuning,
+        sample_rate: u32,
+    ) -> Result<Self, String> {
+        Ok(Sequence::with_voices(
+            read_midi(data, tracks, channels, tuning, sample_rate)?,
+            sample_rate,
+        ))
+    }
+
+    /// Read a MusicXML score into a sequence, see [`read_musicxml`].
+    pub fn from_musicxml(
+        source: &str,
+        tuning: &Tuning,
+        tempo: u32,
+        sample_rate: u32,
+    ) -> Result<Self, String> {
+        Ok(Sequence::with_voices(
+            read_musicxml(source, tuning, tempo, sample_rate)?,
+            sample_rate,
+        ))
+    }
+
+    pub fn voices(&self) -> &[Voice] {
+        &self.voices
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Largest number of pitches played at once over all voices.
+    pub fn polyphony(&self) -> usize {
+        self.voices.iter().map(Voice::polyphony).sum()
+    }
+
+    /// Hold the last note of every voice indefinitely.
+    pub fn hold_last_note(&mut self) {
+        for voice in &mut self.voices {
+            if let Some(last) = voice.notes.last_mut() {
+                last.num_samples = 0;
+            }
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::note::A4_MIDI_KEY;
+use crate::scala::Scala;
+
+/// Temperament that the twelve pitch classes are tuned in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Temperament {
+    /// Twelve-tone equal temperament, where every semitone is 100 cents.
+    Equal,
+    /// Five-limit just intonation, with pure thirds and fifths above the tonic.
+    Just,
+    /// Pythagorean tuning from a chain of pure fifths, with the wolf fifth between G# and Eb
+    /// when the tonic is C.
+    Pythagorean,
+    /// Quarter-comma meantone, with pure major thirds and the wolf fifth between G# and Eb
+    /// when the tonic is C.
+    Meantone,
+    /// Werckmeister III well temperament.
+    Werckmeister,
+    /// Kirnberger III well temperament.
+    Kirnberger,
+}
+
+impl Temperament {
+    /// Pitches of the twelve semitones above the tonic in cents, from the tonic at 0.
+    fn cents(self) -> [f32; 12] {
+        match self {
+            Temperament::Equal => [
+                0.0, 100.0, 200.0, 300.0, 400.0, 500.0, 600.0, 700.0, 800.0, 900.0, 1000.0, 1100.0,
+            ],
+            // 1/1 16/15 9/8 6/5 5/4 4/3 45/32 3/2 8/5 5/3 9/5 15/8
+            Temperament::Just => [
+                0.0, 111.73, 203.91, 315.64, 386.31, 498.05, 590.22, 701.96, 813.69, 884.36,
+                1017.60, 1088.27,
+            ],
+            // 1/1 2187/2048 9/8 32/27 81/64 4/3 729/512 3/2 6561/4096 27/16 16/9 243/128
+            Temperament::Pythagorean => [
+                0.0, 113.69, 203.91, 294.13, 407.82, 498.05, 611.73, 701.96, 815.64, 905.87,
+                996.09, 1109.78,
+            ],
+            // Fifths narrowed by a quarter of the syntonic comma, from Eb to G# above C
+            Temperament::Meantone => [
+                0.0, 76.05, 193.16, 310.26, 386.31, 503.42, 579.47, 696.58, 772.63, 889.74,
+                1006.84, 1082.89,
+            ],
+            // Fifths C-G-D-A and B-F# narrowed by a quarter of the Pythagorean comma
+            Temperament::Werckmeister => [
+                0.0, 90.22, 192.18, 294.13, 390.23, 498.05, 588.27, 696.09, 792.18, 888.27, 996.09,
+                1092.18,
+            ],
+            // Fifths C-G-D-A-E narrowed by a quarter of the syntonic comma, and F#-C# by a
+            // schisma
+            Temperament::Kirnberger => [
+                0.0, 90.22, 193.16, 294.13, 386.31, 498.05, 590.22, 696.58, 792.18, 889.74, 996.09,
+                1088.27,
+            ],
+        }
+    }
+}
+
+impl FromStr for Temperament {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "equal" => Ok(Temperament::Equal),
+            "just" => Ok(Temperament::Just),
+            "pythagorean" => Ok(Temperament::Pythagorean),
+            "meantone" => Ok(Temperament::Meantone),
+            "werckmeister" => Ok(Temperament::Werckmeister),
+            "kirnberger" => Ok(Temperament::Kirnberger),
+            other => Err(format!(
+                "Invalid temperament '{other}'. Must be one of equal, just, pythagorean, \
+                meantone, werckmeister or kirnberger."
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Temperament {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Temperament::Equal => "equal",
+            Temperament::Just => "just",
+            Temperament::Pythagorean => "pythagorean",
+            Temperament::Meantone => "meantone",
+            Temperament::Werckmeister => "werckmeister",
+            Temperament::Kirnberger => "kirnberger",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Tuning of the pitches of notes written as note names.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tuning {
+    /// Frequency of A4 in Hz, which keeps its pitch in every temperament.
+    pub reference: f32,
+    pub temperament: Temperament,
+    /// Pitch class that the temperament is built on, in semitones above A from 0 to 11,
+    /// e.g. 3 for C.
+    pub tonic: i32,
+    /// Scala scale that replaces the temperament, where the pitches of notes are mapped to
+    /// MIDI keys and then to the scale degrees of the keys.
+    pub scala: Option<Scala>,
+}
+
+impl Tuning {
+    /// Equal temperament where A4 has the frequency `reference`.
+    pub fn new(reference: f32) -> Self {
+        Tuning::with_temperament(reference, Temperament::Equal, 3)
+    }
+
+    /// Tuning in `temperament` built on the pitch class `tonic` semitones above A, where A4
+    /// has the frequency `reference`.
+    pub fn with_temperament(reference: f32, temperament: Temperament, tonic: i32) -> Self {
+        Tuning {
+            reference,
+            temperament,
+            tonic: tonic.rem_euclid(12),
+            scala: None,
+        }
+    }
+
+    /// Tuning of a Scala scale.
+    pub fn with_scala(scala: Scala) -> Self {
+        Tuning {
+            reference: scala.mapping.reference_frequency as f32,
+            scala: Some(scala),
+            ..Tuning::new(440.0)
+        }
+    }
+
+    /// Pitch in cents of the pitch `semitones` from A4, relative to the tonic below A4.
+    fn cents(&self, semitones: i32) -> f32 {
+        let above_tonic = semitones - self.tonic;
+        1200.0 * above_tonic.div_euclid(12) as f32
+            + self.temperament.cents()[above_tonic.rem_euclid(12) as usize]
+    }
+
+    /// Frequency of the pitch `semitones` from A4, which fails if a Scala keyboard mapping
+    /// leaves its key unmapped.
+    pub fn frequency(&self, semitones: i32) -> Result<f32, String> {
+        match &self.scala {
+            Some(scala) => scala.key_frequency(semitones + A4_MIDI_KEY),
+            None => {
+                let cents = self.cents(semitones) - self.cents(0);
+                Ok(2f32.powf(cents / 1200.0) * self.reference)
+            }
+        }
+    }
+
+    /// Frequency of `degree` of the Scala scale, counted from the degree of its middle key.
+    pub fn degree_frequency(&self, degree: i32) -> Result<f32, String> {
+        match &self.scala {
+            Some(scala) => Ok(scala.degree_frequency(degree)),
+            None => Err(format!(
+                "Scale degree {degree} can only be played with a Scala scale"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tonic of the temperaments, in semitones above A.
+    const C: i32 = 3;
+
+    fn assert_frequency(tuning: &Tuning, semitones: i32, expected: f32) {
+        let frequency = tuning.frequency(semitones).unwrap();
+        assert!(
+            (frequency - expected).abs() < 0.01,
+            "{semitones} semitones from A4 is {frequency} Hz, not {expected} Hz"
+        );
+    }
+
+    /// Interval in cents between the pitches `low` and `high` semitones from A4.
+    fn interval(tuning: &Tuning, low: i32, high: i32) -> f32 {
+        let ratio = tuning.frequency(high).unwrap() / tuning.frequency(low).unwrap();
+        1200.0 * ratio.log2()
+    }
+
+    #[test]
+    fn equal_temperament() {
+        let tuning = Tuning::new(440.0);
+        assert_frequency(&tuning, 0, 440.0);
+        assert_frequency(&tuning, 12, 880.0);
+        assert_frequency(&tuning, -9, 261.63);
+        assert_frequency(&tuning, 3, 523.25);
+        assert_frequency(&Tuning::new(432.0), -12, 216.0);
+        // The tonic makes no difference in equal temperament
+        let tuning = Tuning::with_temperament(440.0, Temperament::Equal, 7);
+        assert_frequency(&tuning, -9, 261.63);
+    }
+
+    #[test]
+    fn just_intonation() {
+        let tuning = Tuning::with_temperament(440.0, Temperament::Just, C);
+        // A4 keeps its pitch, and the rest of the scale is tuned to it through the tonic
+        assert_frequency(&tuning, 0, 440.0);
+        assert_frequency(&tuning, -5, 330.0);
+        assert_frequency(&tuning, -9, 264.0);
+        assert_frequency(&tuning, -2, 396.0);
+        assert_frequency(&tuning, 3, 528.0);
+        assert_frequency(&tuning, -21, 132.0);
+        // Pure major third and fifth above the tonic
+        assert!((interval(&tuning, -9, -5) - 386.31).abs() < 0.02);
+        assert!((interval(&tuning, -9, -2) - 701.96).abs() < 0.02);
+
+        // Built on A, C# is a pure major third above A4
+        let tuning = Tuning::with_temperament(440.0, Temperament::Just, 0);
+        assert_frequency(&tuning, 4, 550.0);
+        assert_frequency(&tuning, 7, 660.0);
+    }
+
+    #[test]
+    fn pythagorean_tuning() {
+        let tuning = Tuning::with_temperament(440.0, Temperament::Pythagorean, C);
+        assert_frequency(&tuning, 0, 440.0);
+        assert_frequency(&tuning, -5, 330.0);
+        // Every fifth but the wolf fifth G#-Eb is pure, which is narrower by the
+        // Pythagorean comma
+        for low in (-9..=2).filter(|&low| low != -1) {
+            assert!((interval(&tuning, low, low + 7) - 701.96).abs() < 0.02);
+        }
+        assert!((interval(&tuning, -1, 6) - 678.49).abs() < 0.02);
+        // Its major thirds are wider than pure ones
+        assert!((interval(&tuning, -9, -5) - 407.82).abs() < 0.02);
+    }
+
+    #[test]
+    fn meantone() {
+        let tuning = Tuning::with_temperament(440.0, Temperament::Meantone, C);
+        assert_frequency(&tuning, 0, 440.0);
+        // Pure major thirds and narrow fifths
+        assert!((interval(&tuning, -9, -5) - 386.31).abs() < 0.02);
+        assert!((interval(&tuning, -4, 0) - 386.31).abs() < 0.02);
+        assert!((interval(&tuning, -9, -2) - 696.58).abs() < 0.02);
+    }
+
+    #[test]
+    fn well_temperaments() {
+        for temperament in [Temperament::Werckmeister, Temperament::Kirnberger] {
+            let tuning = Tuning::with_temperament(440.0, temperament, C);
+            assert_frequency(&tuning, 0, 440.0);
+            // Every fifth is playable, unlike the wolf fifths of meantone
+            for low in -9..3 {
+                let fifth = interval(&tuning, low, low + 7);
+                assert!(
+                    (fifth - 700.0).abs() < 25.0,
+                    "{temperament} fifth of {fifth}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn temperament_names() {
+        for temperament in [
+            Temperament::Equal,
+            Temperament::Just,
+            Temperament::Pythagorean,
+            Temperament::Meantone,
+            Temperament::Werckmeister,
+            Temperament::Kirnberger,
+        ] {
+            assert_eq!(temperament.to_string().parse(), Ok(temperament));
+        }
+        assert_eq!("Just".parse(), Ok(Temperament::Just));
+        assert!("mean".parse::<Temperament>().is_err());
+    }
+
+    #[test]
+    fn tonics() {
+        assert_eq!(
+            Tuning::with_temperament(440.0, Temperament::Just, -9).tonic,
+            C
+        );
+        assert_eq!(
+            Tuning::with_temperament(440.0, Temperament::Just, 15).tonic,
+            C
+        );
+        assert!(Tuning::new(440.0).degree_frequency(1).is_err());
+    }
+}
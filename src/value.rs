@@ -0,0 +1,128 @@
+use std::cmp::Ordering;
+use std::time::Duration;
+
+/// Length of a note as an exact fraction of a whole note.
+///
+/// Keeping note values as fractions instead of durations means tuplets and dotted notes can
+/// be summed without rounding, and only the absolute position in a sequence is ever rounded
+/// to a sample.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct NoteValue {
+    numerator: u64,
+    denominator: u64,
+}
+
+pub(crate) fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+impl NoteValue {
+    pub fn new(numerator: u64, denominator: u64) -> Self {
+        assert!(denominator != 0, "Note value denominator must not be zero");
+        let divisor = gcd(numerator as u128, denominator as u128).max(1) as u64;
+        NoteValue {
+            numerator: numerator / divisor,
+            denominator: denominator / divisor,
+        }
+    }
+
+    /// Reduce a fraction computed in wider precision, or `None` if it does not fit.
+    fn reduced(numerator: u128, denominator: u128) -> Option<Self> {
+        let divisor = gcd(numerator, denominator).max(1);
+        Some(NoteValue {
+            numerator: u64::try_from(numerator / divisor).ok()?,
+            denominator: u64::try_from(denominator / divisor).ok()?,
+        })
+    }
+
+    /// Sum of two note values, or `None` if the result can not be represented.
+    pub fn checked_add(self, other: NoteValue) -> Option<NoteValue> {
+        NoteValue::reduced(
+            self.numerator as u128 * other.denominator as u128
+                + other.numerator as u128 * self.denominator as u128,
+            self.denominator as u128 * other.denominator as u128,
+        )
+    }
+
+    /// Difference of two note values, or `None` if `other` is longer than `self`.
+    pub fn checked_sub(self, other: NoteValue) -> Option<NoteValue> {
+        NoteValue::reduced(
+            (self.numerator as u128 * other.denominator as u128)
+                .checked_sub(other.numerator as u128 * self.denominator as u128)?,
+            self.denominator as u128 * other.denominator as u128,
+        )
+    }
+
+    /// Product of two note values, or `None` if the result can not be represented.
+    pub fn checked_mul(self, other: NoteValue) -> Option<NoteValue> {
+        NoteValue::reduced(
+            self.numerator as u128 * other.numerator as u128,
+            self.denominator as u128 * other.denominator as u128,
+        )
+    }
+
+    pub fn zero() -> Self {
+        NoteValue::new(0, 1)
+    }
+
+    pub fn whole() -> Self {
+        NoteValue::new(1, 1)
+    }
+
+    /// Factor by which `dots` dots extend a note value, i.e. 1 + 1/2 + 1/4 + ... + 1/2^dots,
+    /// e.g. 3/2 for a single dot. Callers limit `dots` to well below 63.
+    pub(crate) fn dotted(dots: u32) -> Self {
+        NoteValue::new((1 << (dots + 1)) - 1, 1 << dots)
+    }
+
+    pub fn numerator(&self) -> u64 {
+        self.numerator
+    }
+
+    pub fn denominator(&self) -> u64 {
+        self.denominator
+    }
+
+    /// Length of the note value at the given tempo, where a beat is a quarter note.
+    pub fn to_duration(self, tempo: u32) -> Duration {
+        let nanos = self.numerator as u128 * 240 * 1_000_000_000
+            / (self.denominator as u128 * tempo as u128);
+        Duration::from_nanos(nanos as u64)
+    }
+
+    /// Number of samples spanned by the note value, rounded to the nearest sample.
+    pub fn to_samples(self, tempo: u32, sample_rate: u32) -> u128 {
+        let num = self.numerator as u128 * 240 * sample_rate as u128;
+        let den = self.denominator as u128 * tempo as u128;
+        (2 * num + den) / (2 * den)
+    }
+
+    /// Number of MIDI ticks spanned by the note value, rounded to the nearest tick.
+    pub fn to_ticks(self, ticks_per_quarter: u16) -> u128 {
+        let num = self.numerator as u128 * 4 * ticks_per_quarter as u128;
+        let den = self.denominator as u128;
+        (2 * num + den) / (2 * den)
+    }
+}
+
+impl Default for NoteValue {
+    fn default() -> Self {
+        NoteValue::zero()
+    }
+}
+
+impl PartialOrd for NoteValue {
+    fn partial_cmp(&self, other: &NoteValue) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for NoteValue {
+    fn cmp(&self, other: &NoteValue) -> Ordering {
+        (self.numerator as u128 * other.denominator as u128)
+            .cmp(&(other.numerator as u128 * self.denominator as u128))
+    }
+}
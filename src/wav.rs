@@ -0,0 +1,101 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+
+use crate::player::Player;
+
+/// Sample encoding of a rendered WAV file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BitDepth {
+    Int16,
+    Int24,
+    Int32,
+    Float32,
+}
+
+impl BitDepth {
+    fn spec(self, channels: u16, sample_rate: u32) -> WavSpec {
+        let (bits_per_sample, sample_format) = match self {
+            BitDepth::Int16 => (16, SampleFormat::Int),
+            BitDepth::Int24 => (24, SampleFormat::Int),
+            BitDepth::Int32 => (32, SampleFormat::Int),
+            BitDepth::Float32 => (32, SampleFormat::Float),
+        };
+        WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample,
+            sample_format,
+        }
+    }
+}
+
+impl FromStr for BitDepth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "16" => Ok(BitDepth::Int16),
+            "24" => Ok(BitDepth::Int24),
+            "32" => Ok(BitDepth::Int32),
+            "32f" | "float" => Ok(BitDepth::Float32),
+            other => Err(format!(
+                "Invalid bit depth '{other}'. Must be one of 16, 24, 32 or 32f."
+            )),
+        }
+    }
+}
+
+impl fmt::Display for BitDepth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            BitDepth::Int16 => "16",
+            BitDepth::Int24 => "24",
+            BitDepth::Int32 => "32",
+            BitDepth::Float32 => "32f",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Scale a sample in the range [-1, 1] to a signed integer of the given bit width.
+fn to_int(sample: f32, bits: u32) -> i32 {
+    let max = ((1i64 << (bits - 1)) - 1) as f64;
+    (sample.clamp(-1.0, 1.0) as f64 * max).round() as i32
+}
+
+/// Render every frame of `player` into a WAV file of `channels` channels at `path`, see
+/// [`Player::write_frame`] for how the channels are filled.
+///
+/// The player must eventually finish, i.e. the sequence can not hold its last note.
+pub fn write_wav<P: AsRef<Path>>(
+    path: P,
+    player: &mut Player,
+    channels: u16,
+    bit_depth: BitDepth,
+) -> Result<(), String> {
+    if channels == 0 {
+        return Err(String::from("Cannot write a WAV file without any channels"));
+    }
+    let path = path.as_ref();
+    let spec = bit_depth.spec(channels, player.sample_rate());
+    let mut writer = WavWriter::create(path, spec)
+        .map_err(|e| format!("Failed to create WAV file '{}': {e}", path.display()))?;
+
+    let write_err = |e: hound::Error| format!("Failed to write WAV file '{}': {e}", path.display());
+    let mut frame = vec![0.0; channels as usize];
+    while player.write_frame(&mut frame) {
+        for &sample in &frame {
+            match bit_depth {
+                BitDepth::Int16 => writer.write_sample(to_int(sample, 16) as i16),
+                BitDepth::Int24 => writer.write_sample(to_int(sample, 24)),
+                BitDepth::Int32 => writer.write_sample(to_int(sample, 32)),
+                BitDepth::Float32 => writer.write_sample(sample),
+            }
+            .map_err(write_err)?;
+        }
+    }
+    writer.finalize().map_err(write_err)
+}
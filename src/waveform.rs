@@ -0,0 +1,282 @@
+use std::f32::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+/// Duty cycle of a pulse wave when none is given.
+const DEFAULT_DUTY_CYCLE: f32 = 0.25;
+
+/// Shape of the oscillator playing a note.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Waveform {
+    Sine,
+    Square,
+    Sawtooth,
+    Triangle,
+    /// Pulse wave which is high for the given fraction of each period.
+    Pulse(f32),
+    /// White noise, which ignores the pitch of the note.
+    Noise,
+}
+
+/// Correction for a step discontinuity at phase 0, smoothing it over one sample on each side.
+fn poly_blep(phase: f32, phase_increment: f32) -> f32 {
+    if phase < phase_increment {
+        let x = phase / phase_increment;
+        2.0 * x - x * x - 1.0
+    } else if phase > 1.0 - phase_increment {
+        let x = (phase - 1.0) / phase_increment;
+        x * x + 2.0 * x + 1.0
+    } else {
+        0.0
+    }
+}
+
+/// Correction for a discontinuity in slope at phase 0, i.e. the integral of [`poly_blep`].
+fn poly_blamp(phase: f32, phase_increment: f32) -> f32 {
+    if phase < phase_increment {
+        let x = phase / phase_increment - 1.0;
+        -x * x * x / 3.0
+    } else if phase > 1.0 - phase_increment {
+        let x = (phase - 1.0) / phase_increment + 1.0;
+        x * x * x / 3.0
+    } else {
+        0.0
+    }
+}
+
+impl Waveform {
+    /// Get the value of the waveform at `phase` in [0, 1), where `phase_increment` is the
+    /// change in phase per sample, i.e. frequency divided by sample rate.
+    ///
+    /// Discontinuities are smoothed with polynomial band-limited steps, which suppresses the
+    /// aliasing a naive implementation produces at high frequencies.
+    pub fn sample(self, phase: f32, phase_increment: f32, noise: &mut Noise) -> f32 {
+        let dt = phase_increment;
+        match self {
+            Waveform::Sine => (2.0 * PI * phase).sin(),
+            Waveform::Square => Waveform::Pulse(0.5).sample(phase, dt, noise),
+            Waveform::Sawtooth => 2.0 * phase - 1.0 - poly_blep(phase, dt),
+            Waveform::Triangle => {
+                let mut value = 4.0 * phase;
+                if value >= 3.0 {
+                    value -= 4.0;
+                } else if value > 1.0 {
+                    value = 2.0 - value;
+                }
+                let rising = (phase + 0.25).fract();
+                let falling = (phase + 0.75).fract();
+                value + 2.0 * dt * (poly_blamp(rising, dt) - poly_blamp(falling, dt))
+            }
+            Waveform::Pulse(duty_cycle) => {
+                let value = if phase < duty_cycle { 1.0 } else { -1.0 };
+                let falling = (phase + 1.0 - duty_cycle).fract();
+                // Centre the pulse on zero, since its mean is 2 * duty_cycle - 1
+                value + poly_blep(phase, dt) - poly_blep(falling, dt) - (2.0 * duty_cycle - 1.0)
+            }
+            Waveform::Noise => noise.next_sample(),
+        }
+    }
+}
+
+impl FromStr for Waveform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, duty_cycle) = match s.split_once(':') {
+            Some((name, duty_cycle)) => (name, Some(duty_cycle)),
+            None => (s, None),
+        };
+        match (name.to_lowercase().as_str(), duty_cycle) {
+            ("sine" | "sin", None) => Ok(Waveform::Sine),
+            ("square" | "sqr", None) => Ok(Waveform::Square),
+            ("sawtooth" | "saw", None) => Ok(Waveform::Sawtooth),
+            ("triangle" | "tri", None) => Ok(Waveform::Triangle),
+            ("noise", None) => Ok(Waveform::Noise),
+            ("pulse", None) => Ok(Waveform::Pulse(DEFAULT_DUTY_CYCLE)),
+            ("pulse", Some(duty_cycle)) => match duty_cycle.parse::<f32>() {
+                Ok(duty_cycle) if duty_cycle > 0.0 && duty_cycle < 1.0 => {
+                    Ok(Waveform::Pulse(duty_cycle))
+                }
+                _ => Err(format!(
+                    "Invalid duty cycle '{duty_cycle}'. Must be a number between 0 and 1, \
+                    e.g. pulse:0.25."
+                )),
+            },
+            _ => Err(format!(
+                "Invalid waveform '{s}'. Must be one of sine, square, sawtooth, triangle, \
+                pulse:<duty cycle> or noise."
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Waveform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Waveform::Sine => write!(f, "sine"),
+            Waveform::Square => write!(f, "square"),
+            Waveform::Sawtooth => write!(f, "sawtooth"),
+            Waveform::Triangle => write!(f, "triangle"),
+            Waveform::Pulse(duty_cycle) => write!(f, "pulse:{duty_cycle}"),
+            Waveform::Noise => write!(f, "noise"),
+        }
+    }
+}
+
+/// Phase accumulator generating a waveform at a frequency that can change between samples
+/// without discontinuities.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Oscillator {
+    /// Position within the current period, in [0, 1). Kept in double precision so that the
+    /// pitch stays accurate however long a note is held.
+    phase: f64,
+}
+
+impl Oscillator {
+    pub fn new() -> Self {
+        Oscillator::default()
+    }
+
+    /// Get the value of `waveform` at the current phase and move the phase forward by one
+    /// sample at `frequency`.
+    pub fn next_sample(
+        &mut self,
+        waveform: Waveform,
+        frequency: f32,
+        sample_rate: u32,
+        noise: &mut Noise,
+    ) -> f32 {
+        let phase_increment = frequency as f64 / sample_rate as f64;
+        let sample = waveform.sample(self.phase as f32, phase_increment as f32, noise);
+        self.phase = (self.phase + phase_increment).fract();
+        sample
+    }
+}
+
+/// Xorshift generator of white noise in [-1, 1].
+#[derive(Clone, Debug)]
+pub struct Noise {
+    state: u32,
+}
+
+impl Noise {
+    pub fn new(seed: u32) -> Self {
+        // Xorshift gets stuck at zero
+        Noise { state: seed.max(1) }
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    /// Samples of one second of `waveform` at `frequency`.
+    fn render(waveform: Waveform, frequency: f32) -> Vec<f32> {
+        let mut oscillator = Oscillator::new();
+        let mut noise = Noise::new(1);
+        (0..SAMPLE_RATE)
+            .map(|_| oscillator.next_sample(waveform, frequency, SAMPLE_RATE, &mut noise))
+            .collect()
+    }
+
+    /// Triangle with the same phase as [`Waveform::Triangle`], summed from its odd harmonics
+    /// below the Nyquist frequency.
+    fn ideal_triangle(phase: f64, frequency: f64) -> f64 {
+        let harmonics = (SAMPLE_RATE as f64 / 2.0 / frequency) as u32;
+        (1..=harmonics)
+            .step_by(2)
+            .map(|k| {
+                let sign = if k % 4 == 1 { 1.0 } else { -1.0 };
+                sign * (2.0 * PI * k as f64 * phase).sin() / (k * k) as f64
+            })
+            .sum::<f64>()
+            * 8.0
+            / (PI * PI)
+    }
+
+    /// Root mean square difference between `samples` and the ideal triangle at `frequency`.
+    fn triangle_error(samples: &[f32], frequency: f32) -> f64 {
+        let sum: f64 = samples
+            .iter()
+            .enumerate()
+            .map(|(i, &sample)| {
+                let phase = (i as f64 * frequency as f64 / SAMPLE_RATE as f64).fract();
+                (sample as f64 - ideal_triangle(phase, frequency as f64)).powi(2)
+            })
+            .sum();
+        (sum / samples.len() as f64).sqrt()
+    }
+
+    #[test]
+    fn band_limited_triangle() {
+        for frequency in [1234.5, 4321.0, 9000.0] {
+            let naive: Vec<f32> = (0..SAMPLE_RATE)
+                .map(|i| {
+                    let phase = (i as f64 * frequency as f64 / SAMPLE_RATE as f64).fract();
+                    let value = 4.0 * phase as f32;
+                    match value {
+                        value if value >= 3.0 => value - 4.0,
+                        value if value > 1.0 => 2.0 - value,
+                        value => value,
+                    }
+                })
+                .collect();
+            let naive_error = triangle_error(&naive, frequency);
+            let error = triangle_error(&render(Waveform::Triangle, frequency), frequency);
+            assert!(
+                error < naive_error,
+                "Error {error} at {frequency} Hz is not below the naive error {naive_error}"
+            );
+        }
+    }
+
+    #[test]
+    fn pulse_is_centred() {
+        for duty_cycle in [0.1, 0.25, 0.5, 0.9] {
+            let samples = render(Waveform::Pulse(duty_cycle), 100.0);
+            let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+            assert!(mean.abs() < 0.01, "Mean of pulse:{duty_cycle} is {mean}");
+            let peak = samples
+                .iter()
+                .fold(0f32, |peak, sample| peak.max(sample.abs()));
+            let expected = 1.0 + (2.0 * duty_cycle - 1.0).abs();
+            assert!(
+                (peak - expected).abs() < 0.05,
+                "Peak of pulse:{duty_cycle} is {peak}"
+            );
+        }
+    }
+
+    #[test]
+    fn waveform_names() {
+        for name in [
+            "sine",
+            "square",
+            "sawtooth",
+            "triangle",
+            "pulse:0.25",
+            "noise",
+        ] {
+            assert_eq!(name.parse::<Waveform>().unwrap().to_string(), name);
+        }
+        assert_eq!("saw".parse(), Ok(Waveform::Sawtooth));
+        assert_eq!("pulse".parse(), Ok(Waveform::Pulse(DEFAULT_DUTY_CYCLE)));
+        for name in ["", "pulse:0", "pulse:1", "pulse:x", "sine:0.5", "organ"] {
+            assert!(
+                name.parse::<Waveform>().is_err(),
+                "'{name}' should be invalid"
+            );
+        }
+    }
+}